@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+use serde::Deserialize;
+use serbf::schema::Schema;
+use serbf::{BitPacking, CharEncoding, Checksum, Compat, Endian, Envelope, IntEncoding, SerbfConfig, SerbfDeserializer, SerbfError, SerbfSerializer, StructEncoding};
+
+const USAGE: &str = "\
+Usage: serbf <command> [options] [file]
+
+Commands:
+  dump        Annotated hex dump: offsets, bytes and decoded values
+  to-json     Decodes serbf to JSON
+  from-json   Encodes JSON to serbf
+
+Reads the file, or stdin without one, and writes to stdout. Input starting with an envelope
+header is decoded with the header's config and the config options are ignored.
+
+Options:
+  --schema <file>     Schema of compact data, the JSON form of serbf::schema::Schema
+  --envelope          from-json: start the output with an envelope header
+  --self-describing   Tagged encoding
+  --varint            Varint integers
+  --big-endian        Big-endian fixed-width integers
+  --length-prefixed   Length-prefixed structs
+  --canonical         Canonical encoding
+  --strict            Reject varints that aren't minimally encoded
+  --checksum <kind>   Checksum trailer: crc32c or xxhash64
+  --intern-strings    Repeated strings refer to their first occurrence
+  --bit-packing <per> Pack bools and tags into shared bytes: struct or message
+  --char-encoding <e> Chars as str, utf8 or scalar
+  --bincode           bincode v1 compatible layout
+  --postcard          postcard compatible layout, implies --varint
+";
+
+enum Command {
+    Dump,
+    ToJson,
+    FromJson,
+}
+
+struct Args {
+    command: Command,
+    input: Option<String>,
+    schema: Option<Schema>,
+    config: SerbfConfig,
+    envelope: bool,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(v) => { v }
+        Err(e) => {
+            eprintln!("serbf: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(args) {
+        Ok(_) => { ExitCode::SUCCESS }
+        Err(e) => {
+            eprintln!("serbf: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, SerbfError> {
+    let command = match args.next().as_deref() {
+        Some("dump") => { Command::Dump }
+        Some("to-json") => { Command::ToJson }
+        Some("from-json") => { Command::FromJson }
+        Some(v) => { return Err(SerbfError::Message(format!("Unknown command {}", v))) }
+        None => { return Err(SerbfError::Message("Missing command".to_string())) }
+    };
+
+    let mut parsed = Args {
+        command,
+        input: None,
+        schema: None,
+        config: SerbfConfig::default(),
+        envelope: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--schema" => {
+                let path = match args.next() {
+                    Some(v) => { v }
+                    None => { return Err(SerbfError::Message("--schema needs a file".to_string())) }
+                };
+
+                parsed.schema = Some(from_json(&read(Some(&path))?)?);
+            }
+            "--checksum" => {
+                parsed.config.checksum = match args.next().as_deref() {
+                    Some("crc32c") => { Checksum::Crc32c }
+                    Some("xxhash64") => { Checksum::XxHash64 }
+                    _ => { return Err(SerbfError::Message("--checksum needs crc32c or xxhash64".to_string())) }
+                };
+            }
+            "--bit-packing" => {
+                parsed.config.bit_packing = match args.next().as_deref() {
+                    Some("struct") => { BitPacking::PerStruct }
+                    Some("message") => { BitPacking::PerMessage }
+                    _ => { return Err(SerbfError::Message("--bit-packing needs struct or message".to_string())) }
+                };
+            }
+            "--char-encoding" => {
+                parsed.config.char_encoding = match args.next().as_deref() {
+                    Some("str") => { CharEncoding::Str }
+                    Some("utf8") => { CharEncoding::Utf8 }
+                    Some("scalar") => { CharEncoding::Scalar }
+                    _ => { return Err(SerbfError::Message("--char-encoding needs str, utf8 or scalar".to_string())) }
+                };
+            }
+            "--intern-strings" => { parsed.config.intern_strings = true }
+            "--envelope" => { parsed.envelope = true }
+            "--self-describing" => { parsed.config.self_describing = true }
+            "--varint" => { parsed.config.int_encoding = IntEncoding::Varint }
+            "--big-endian" => { parsed.config.endian = Endian::Big }
+            "--length-prefixed" => { parsed.config.struct_encoding = StructEncoding::LengthPrefixed }
+            "--canonical" => { parsed.config.canonical = true }
+            "--strict" => { parsed.config.strict = true }
+            "--bincode" => { parsed.config.compat = Compat::Bincode }
+            "--postcard" => {
+                parsed.config.compat = Compat::Postcard;
+                parsed.config.int_encoding = IntEncoding::Varint;
+            }
+            v if v.starts_with("--") => { return Err(SerbfError::Message(format!("Unknown option {}", v))) }
+            _ if parsed.input.is_some() => { return Err(SerbfError::Message("Only one input file is supported".to_string())) }
+            _ => { parsed.input = Some(arg) }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn run(args: Args) -> Result<(), SerbfError> {
+    let data = read(args.input.as_deref())?;
+    let enveloped = data.starts_with(&Envelope::MAGIC);
+
+    let mut stdout = std::io::stdout().lock();
+
+    match args.command {
+        Command::Dump => {
+            match enveloped {
+                true => { serbf::dump_with_envelope(&data, args.schema.as_ref(), &mut stdout) }
+                false => { serbf::dump(&data, args.config, args.schema.as_ref(), &mut stdout) }
+            }
+        }
+        Command::ToJson => {
+            let mut deserializer = match enveloped {
+                true => { SerbfDeserializer::with_envelope(&data)?.0 }
+                false => { SerbfDeserializer::with_config(&data, args.config)? }
+            };
+
+            // Nothing is written until the whole input is known to be valid
+            let mut out = Vec::new();
+            let mut json = serde_json::Serializer::pretty(&mut out);
+
+            let res = match &args.schema {
+                Some(v) => { serbf::transcode_with_schema(&mut deserializer, v, &mut json) }
+                None if deserializer.config().self_describing => { serbf::transcode(&mut deserializer, &mut json) }
+                None => { return Err(SerbfError::Message("Compact data needs a --schema".to_string())) }
+            };
+
+            match res {
+                Ok(_) => {
+                    deserializer.end()?;
+                    out.push(b'\n');
+
+                    write_all(&mut stdout, &out)
+                }
+                Err(e) => { Err(SerbfError::Message(e.to_string())) }
+            }
+        }
+        Command::FromJson => {
+            if args.schema.is_none() && !args.config.self_describing {
+                return Err(SerbfError::Message("Compact output needs a --schema".to_string()));
+            }
+
+            let mut serializer = match args.envelope {
+                true => { SerbfSerializer::from_writer_with_envelope(Vec::new(), &Envelope::new(args.config))? }
+                false => { SerbfSerializer::from_writer(Vec::new(), args.config) }
+            };
+
+            let mut json = serde_json::Deserializer::from_slice(&data);
+
+            serbf::transcode_from(&mut json, args.schema.as_ref(), &mut serializer)?;
+
+            match json.end() {
+                Ok(_) => { write_all(&mut stdout, &serializer.finish()?) }
+                Err(e) => { Err(SerbfError::Message(format!("Invalid JSON: {}", e))) }
+            }
+        }
+    }
+}
+
+fn read(path: Option<&str>) -> Result<Vec<u8>, SerbfError> {
+    let res = match path {
+        Some(v) => { fs::read(v) }
+        None => {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data).map(|_| data)
+        }
+    };
+
+    match res {
+        Ok(v) => { Ok(v) }
+        Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+    }
+}
+
+fn from_json<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, SerbfError> {
+    match serde_json::from_slice(data) {
+        Ok(v) => { Ok(v) }
+        Err(e) => { Err(SerbfError::Message(format!("Invalid JSON: {}", e))) }
+    }
+}
+
+fn write_all<W: Write>(writer: &mut W, data: &[u8]) -> Result<(), SerbfError> {
+    match writer.write_all(data) {
+        Ok(_) => { Ok(()) }
+        Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+    }
+}
@@ -0,0 +1,130 @@
+use std::hash::Hasher;
+use std::io;
+use std::io::{Error, ErrorKind, Write};
+use std::sync::Arc;
+use twox_hash::XxHash64;
+use crate::error::SerbfError;
+
+/// Integrity trailer appended after a message, always little-endian.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Checksum {
+    #[default]
+    None,
+    Crc32c,
+    XxHash64,
+}
+
+impl Checksum {
+    /// Size of the trailer in bytes.
+    pub fn trailer_len(&self) -> usize {
+        match self {
+            Checksum::None => { 0 }
+            Checksum::Crc32c => { 4 }
+            Checksum::XxHash64 => { 8 }
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Checksum::None
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u64 {
+        match self {
+            Checksum::None => { 0 }
+            Checksum::Crc32c => { crc32c::crc32c(data) as u64 }
+            Checksum::XxHash64 => { XxHash64::oneshot(0, data) }
+        }
+    }
+
+    /// Appends the checksum of everything already in `buf`.
+    pub fn append(&self, buf: &mut Vec<u8>) {
+        let checksum = self.compute(buf).to_le_bytes();
+
+        buf.extend_from_slice(&checksum[..self.trailer_len()]);
+    }
+
+    pub(crate) fn writer<W: Write>(&self, inner: W) -> ChecksumWriter<W> {
+        let state = match self {
+            Checksum::None => { ChecksumState::None }
+            Checksum::Crc32c => { ChecksumState::Crc32c(0) }
+            Checksum::XxHash64 => { ChecksumState::XxHash64(XxHash64::with_seed(0)) }
+        };
+
+        ChecksumWriter {
+            inner,
+            state,
+        }
+    }
+
+    /// Verifies the trailer at the end of `data` and returns the data it covers.
+    pub fn verify<'a>(&self, data: &'a [u8]) -> Result<&'a [u8], SerbfError> {
+        if self.is_none() {
+            return Ok(data);
+        }
+
+        if data.len() < self.trailer_len() {
+            return Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof))));
+        }
+
+        let (payload, trailer) = data.split_at(data.len() - self.trailer_len());
+
+        let mut expected = [0; 8];
+        expected[..trailer.len()].copy_from_slice(trailer);
+        let expected = u64::from_le_bytes(expected);
+
+        let found = self.compute(payload);
+
+        match expected == found {
+            true => { Ok(payload) }
+            false => { Err(SerbfError::ChecksumMismatch { expected, found }) }
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+enum ChecksumState {
+    #[default]
+    None,
+    Crc32c(u32),
+    XxHash64(XxHash64),
+}
+
+/// Keeps a running checksum of everything written through it, for output that isn't buffered.
+#[derive(Clone, Default)]
+pub(crate) struct ChecksumWriter<W> {
+    inner: W,
+    state: ChecksumState,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    /// Writes the trailer and returns the inner writer.
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        let (checksum, len) = match &self.state {
+            ChecksumState::None => { return Ok(self.inner) }
+            ChecksumState::Crc32c(v) => { (*v as u64, 4) }
+            ChecksumState::XxHash64(v) => { (v.finish(), 8) }
+        };
+
+        self.inner.write_all(&checksum.to_le_bytes()[..len])?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+
+        match &mut self.state {
+            ChecksumState::None => {}
+            ChecksumState::Crc32c(v) => { *v = crc32c::crc32c_append(*v, &buf[..len]) }
+            ChecksumState::XxHash64(v) => { v.write(&buf[..len]) }
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
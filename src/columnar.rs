@@ -0,0 +1,839 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use serde::ser::{Impossible, SerializeStruct};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer};
+use varint_rs::VarintWriter;
+use crate::checksum::Checksum;
+use crate::config::SerbfConfig;
+use crate::de::{self, SerbfDeserializer};
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+/// Newtype name that makes serbf's serializer and deserializer lend their config to the columns.
+pub(crate) const TOKEN: &str = "$serbf::private::Columnar";
+
+thread_local! {
+    /// Config of the serbf serializer or deserializer handling the `TOKEN` newtype, if any.
+    static CONFIG: Cell<Option<SerbfConfig>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with `config` lent to the columns it encodes or decodes.
+pub(crate) fn with_config<T>(config: SerbfConfig, f: impl FnOnce() -> T) -> T {
+    let outer = CONFIG.replace(Some(config));
+    let res = f();
+    CONFIG.set(outer);
+
+    res
+}
+
+/// Config of the raw columns, the one of the serbf message around them without its checksum
+/// or the default one in other formats.
+fn raw_config() -> SerbfConfig {
+    match CONFIG.get() {
+        Some(v) => { SerbfConfig { checksum: Checksum::None, ..v } }
+        None => { SerbfConfig::default() }
+    }
+}
+
+/// `Vec<T>` of structs that serializes column by column, see `serialize`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Columnar<T>(pub Vec<T>);
+
+impl<T> Deref for Columnar<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Columnar<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize> Serialize for Columnar<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Columnar<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserialize(deserializer).map(Columnar)
+    }
+}
+
+/// Writes every field of the rows as a column: integers as varints, delta-coded when they never
+/// decrease, strings through a dictionary and all other values as plain serbf, with the config
+/// of the serializer when it's serbf's.
+///
+/// Meant for `#[serde(with = "serbf::columnar")]` on a `Vec` of structs. Every row has to write
+/// the same fields, so `skip_serializing_if` isn't supported, and one of them has to take space.
+pub fn serialize<T, S>(rows: &[T], serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer
+{
+    serializer.serialize_newtype_struct(TOKEN, &Rows(rows))
+}
+
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>
+{
+    deserializer.deserialize_newtype_struct(TOKEN, RowsVisitor(PhantomData))
+}
+
+/// Encodes the columns once the config is known.
+struct Rows<'a, T>(&'a [T]);
+
+impl<T: Serialize> Serialize for Rows<'_, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        let config = raw_config();
+        let mut columns = Vec::new();
+
+        for (i, row) in self.0.iter().enumerate() {
+            let res = row.serialize(RowSerializer { columns: &mut columns, config, first: i == 0, index: 0 });
+
+            if let Err(e) = res {
+                return Err(serde::ser::Error::custom(e));
+            }
+        }
+
+        let encoded = Encoded {
+            rows: self.0.len() as u64,
+            columns: columns.into_iter().map(Column::encode).collect(),
+        };
+
+        if let Err(e) = encoded.check_rows() {
+            return Err(serde::ser::Error::custom(e));
+        }
+
+        encoded.serialize(serializer)
+    }
+}
+
+struct RowsVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for RowsVisitor<T> {
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("columnar rows")
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        let encoded = Encoded::deserialize(deserializer)?;
+        let config = raw_config();
+
+        if let Err(e) = encoded.check_rows() {
+            return Err(serde::de::Error::custom(e));
+        }
+
+        let mut readers = Vec::with_capacity(encoded.columns.len());
+
+        for column in &encoded.columns {
+            match ColumnReader::new(column, config) {
+                Ok(v) => { readers.push(v) }
+                Err(e) => { return Err(serde::de::Error::custom(e)) }
+            }
+        }
+
+        let mut rows = Vec::new();
+
+        for _ in 0..encoded.rows {
+            match T::deserialize(RowDeserializer { columns: &mut readers }) {
+                Ok(v) => { rows.push(v) }
+                Err(e) => { return Err(serde::de::Error::custom(e)) }
+            }
+        }
+
+        Ok(rows)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Encoded {
+    rows: u64,
+    columns: Vec<EncodedColumn>,
+}
+
+impl Encoded {
+    /// Every row takes at least a byte of an integer or string column and a bit of a raw one,
+    /// unless all its values are empty. The row count is untrusted, so it has to fit the
+    /// columns, and rows without any data are rejected since nothing bounds their number.
+    fn check_rows(&self) -> Result<(), SerbfError> {
+        let max = self.columns.iter().filter_map(EncodedColumn::max_rows).min();
+
+        match max {
+            Some(v) if self.rows <= v => { Ok(()) }
+            None if self.rows == 0 => { Ok(()) }
+            Some(_) => { Err(SerbfError::Message(format!("{} rows don't fit the column data", self.rows))) }
+            None => { Err(SerbfError::Message("Columnar rows need a field that isn't empty".to_string())) }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum EncodedColumn {
+    /// Zigzag varints, each one the difference to the previous value if `delta`
+    Ints { kind: u8, delta: bool, data: Bytes },
+
+    /// Varint indices into the dictionary
+    Strs { dictionary: Vec<String>, indices: Bytes },
+
+    /// The values encoded back to back, see `raw_config`
+    Raw { data: Bytes },
+}
+
+impl EncodedColumn {
+    fn max_rows(&self) -> Option<u64> {
+        match self {
+            EncodedColumn::Ints { data, .. } => { Some(data.0.len() as u64) }
+            EncodedColumn::Strs { indices, .. } => { Some(indices.0.len() as u64) }
+            EncodedColumn::Raw { data } if data.0.is_empty() => { None }
+            EncodedColumn::Raw { data } => { Some((data.0.len() as u64).saturating_mul(8)) }
+        }
+    }
+}
+
+pub(crate) struct Bytes(pub(crate) Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("bytes")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error
+    {
+        Ok(Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error
+    {
+        Ok(Bytes(v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let mut bytes = Vec::new();
+
+        while let Some(v) = seq.next_element()? {
+            bytes.push(v);
+        }
+
+        Ok(Bytes(bytes))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IntKind {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl IntKind {
+    fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    fn from_id(id: u8) -> Result<Self, SerbfError> {
+        match id {
+            0 => { Ok(IntKind::I8) }
+            1 => { Ok(IntKind::I16) }
+            2 => { Ok(IntKind::I32) }
+            3 => { Ok(IntKind::I64) }
+            4 => { Ok(IntKind::U8) }
+            5 => { Ok(IntKind::U16) }
+            6 => { Ok(IntKind::U32) }
+            7 => { Ok(IntKind::U64) }
+            _ => { Err(SerbfError::InvalidEnumID) }
+        }
+    }
+
+    fn serialize(&self, v: i128, serializer: &mut SerbfSerializer) -> Result<(), SerbfError> {
+        match self {
+            IntKind::I8 => { narrow::<i8>(v)?.serialize(serializer) }
+            IntKind::I16 => { narrow::<i16>(v)?.serialize(serializer) }
+            IntKind::I32 => { narrow::<i32>(v)?.serialize(serializer) }
+            IntKind::I64 => { narrow::<i64>(v)?.serialize(serializer) }
+            IntKind::U8 => { narrow::<u8>(v)?.serialize(serializer) }
+            IntKind::U16 => { narrow::<u16>(v)?.serialize(serializer) }
+            IntKind::U32 => { narrow::<u32>(v)?.serialize(serializer) }
+            IntKind::U64 => { narrow::<u64>(v)?.serialize(serializer) }
+        }
+    }
+
+    fn deserialize<'de, S>(&self, v: i128, seed: S) -> Result<S::Value, SerbfError>
+    where
+        S: DeserializeSeed<'de>
+    {
+        match self {
+            IntKind::I8 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<i8>(v)?)) }
+            IntKind::I16 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<i16>(v)?)) }
+            IntKind::I32 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<i32>(v)?)) }
+            IntKind::I64 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<i64>(v)?)) }
+            IntKind::U8 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<u8>(v)?)) }
+            IntKind::U16 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<u16>(v)?)) }
+            IntKind::U32 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<u32>(v)?)) }
+            IntKind::U64 => { seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(narrow::<u64>(v)?)) }
+        }
+    }
+}
+
+fn narrow<T: TryFrom<i128>>(v: i128) -> Result<T, SerbfError> {
+    match T::try_from(v) {
+        Ok(v) => { Ok(v) }
+        Err(_) => { Err(SerbfError::Message(format!("Column value out of range: {v}"))) }
+    }
+}
+
+enum Scalar {
+    Int(IntKind, i128),
+    Str(String),
+}
+
+enum Column {
+    Empty,
+    Ints(IntKind, Vec<i128>),
+    Strs(Vec<String>),
+    Raw(Box<SerbfSerializer>),
+}
+
+impl Column {
+    fn push<T>(&mut self, value: &T, config: SerbfConfig) -> Result<(), SerbfError>
+    where
+        T: ?Sized + Serialize
+    {
+        if let Column::Raw(serializer) = self {
+            return value.serialize(&mut **serializer);
+        }
+
+        // Anything the probe doesn't take goes into a raw column
+        let scalar = value.serialize(Probe).unwrap_or(None);
+
+        match (&mut *self, scalar) {
+            (Column::Empty, Some(Scalar::Int(kind, v))) => { *self = Column::Ints(kind, vec![v]) }
+            (Column::Empty, Some(Scalar::Str(v))) => { *self = Column::Strs(vec![v]) }
+            (Column::Ints(kind, values), Some(Scalar::Int(k, v))) if *kind == k => { values.push(v) }
+            (Column::Strs(values), Some(Scalar::Str(v))) => { values.push(v) }
+            _ => {
+                let mut serializer = self.to_raw(config)?;
+                value.serialize(&mut serializer)?;
+
+                *self = Column::Raw(Box::new(serializer));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-encodes the values collected so far as plain serbf.
+    fn to_raw(&self, config: SerbfConfig) -> Result<SerbfSerializer, SerbfError> {
+        let mut serializer = SerbfSerializer::with_config(config);
+
+        match self {
+            Column::Empty => {}
+            Column::Ints(kind, values) => {
+                for v in values {
+                    kind.serialize(*v, &mut serializer)?;
+                }
+            }
+            Column::Strs(values) => {
+                for v in values {
+                    v.serialize(&mut serializer)?;
+                }
+            }
+            Column::Raw(v) => { return Ok((**v).clone()) }
+        }
+
+        Ok(serializer)
+    }
+
+    fn encode(self) -> EncodedColumn {
+        match self {
+            Column::Empty => { EncodedColumn::Raw { data: Bytes(Vec::new()) } }
+            Column::Ints(kind, values) => {
+                let delta = values.windows(2).all(|v| v[0] <= v[1]);
+
+                let mut data = Vec::new();
+                let mut prev = 0;
+
+                for v in values {
+                    // Writing into a Vec can't fail
+                    let _ = match delta {
+                        true => { data.write_i128_varint(v - prev) }
+                        false => { data.write_i128_varint(v) }
+                    };
+
+                    prev = v;
+                }
+
+                EncodedColumn::Ints { kind: kind.id(), delta, data: Bytes(data) }
+            }
+            Column::Strs(values) => {
+                let mut dictionary = Vec::new();
+                let mut positions = HashMap::new();
+                let mut indices = Vec::new();
+
+                for v in values {
+                    let index = *positions.entry(v.clone()).or_insert_with(|| {
+                        dictionary.push(v);
+                        dictionary.len() - 1
+                    });
+
+                    let _ = indices.write_u64_varint(index as u64);
+                }
+
+                EncodedColumn::Strs { dictionary, indices: Bytes(indices) }
+            }
+            Column::Raw(v) => { EncodedColumn::Raw { data: Bytes((*v).into_inner()) } }
+        }
+    }
+}
+
+enum ColumnReader<'c> {
+    Ints { kind: IntKind, delta: bool, data: Cursor<&'c [u8]>, prev: i128 },
+    Strs { dictionary: &'c [String], indices: Cursor<&'c [u8]> },
+    Raw(SerbfDeserializer<'c>),
+}
+
+impl<'c> ColumnReader<'c> {
+    fn new(column: &'c EncodedColumn, config: SerbfConfig) -> Result<Self, SerbfError> {
+        let reader = match column {
+            EncodedColumn::Ints { kind, delta, data } => {
+                ColumnReader::Ints { kind: IntKind::from_id(*kind)?, delta: *delta, data: Cursor::new(&data.0), prev: 0 }
+            }
+            EncodedColumn::Strs { dictionary, indices } => {
+                ColumnReader::Strs { dictionary, indices: Cursor::new(&indices.0) }
+            }
+            EncodedColumn::Raw { data } => {
+                ColumnReader::Raw(SerbfDeserializer::with_config_unchecked(&data.0, config))
+            }
+        };
+
+        Ok(reader)
+    }
+
+    fn next<S>(&mut self, seed: S) -> Result<S::Value, SerbfError>
+    where
+        S: DeserializeSeed<'c>
+    {
+        match self {
+            ColumnReader::Ints { kind, delta, data, prev } => {
+                let v = de::read_zigzag::<i128, _>(data, false)?;
+
+                let v = match delta {
+                    true => {
+                        match prev.checked_add(v) {
+                            Some(v) => { v }
+                            None => { return Err(SerbfError::Message("Delta overflows i128".to_string())) }
+                        }
+                    }
+                    false => { v }
+                };
+
+                *prev = v;
+
+                kind.deserialize(v, seed)
+            }
+            ColumnReader::Strs { dictionary, indices } => {
+                let index = de::read_varint::<u64, _>(indices, false)?;
+
+                match usize::try_from(index).ok().and_then(|v| dictionary.get(v)) {
+                    Some(v) => { seed.deserialize(BorrowedStrDeserializer::new(v)) }
+                    None => { Err(SerbfError::Message(format!("Dictionary index out of range: {index}"))) }
+                }
+            }
+            ColumnReader::Raw(de) => { seed.deserialize(de) }
+        }
+    }
+}
+
+fn not_a_struct() -> SerbfError {
+    SerbfError::Message("Columnar rows have to be structs".to_string())
+}
+
+struct RowSerializer<'a> {
+    columns: &'a mut Vec<Column>,
+    config: SerbfConfig,
+    first: bool,
+    index: usize,
+}
+
+impl SerializeStruct for RowSerializer<'_> {
+    type Ok = ();
+    type Error = SerbfError;
+
+    fn serialize_field<T>(&mut self, _key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        if self.first {
+            self.columns.push(Column::Empty);
+        }
+
+        let column = match self.columns.get_mut(self.index) {
+            Some(v) => { v }
+            None => { return Err(SerbfError::Message("Columnar rows have to write the same fields".to_string())) }
+        };
+
+        column.push(value, self.config)?;
+        self.index += 1;
+
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        match self.index == self.columns.len() {
+            true => { Ok(()) }
+            false => { Err(SerbfError::Message("Columnar rows have to write the same fields".to_string())) }
+        }
+    }
+}
+
+macro_rules! reject {
+    ($($name:ident($($arg:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $name(self, $(_: $arg),*) -> Result<$ret, Self::Error> {
+                Err(not_a_struct())
+            }
+        )*
+    };
+}
+
+impl<'a> Serializer for RowSerializer<'a> {
+    type Ok = ();
+    type Error = SerbfError;
+    type SerializeSeq = Impossible<(), SerbfError>;
+    type SerializeTuple = Impossible<(), SerbfError>;
+    type SerializeTupleStruct = Impossible<(), SerbfError>;
+    type SerializeTupleVariant = Impossible<(), SerbfError>;
+    type SerializeMap = Impossible<(), SerbfError>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), SerbfError>;
+
+    reject! {
+        serialize_bool(bool) -> ();
+        serialize_i8(i8) -> ();
+        serialize_i16(i16) -> ();
+        serialize_i32(i32) -> ();
+        serialize_i64(i64) -> ();
+        serialize_u8(u8) -> ();
+        serialize_u16(u16) -> ();
+        serialize_u32(u32) -> ();
+        serialize_u64(u64) -> ();
+        serialize_f32(f32) -> ();
+        serialize_f64(f64) -> ();
+        serialize_char(char) -> ();
+        serialize_str(&str) -> ();
+        serialize_bytes(&[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(&'static str) -> ();
+        serialize_unit_variant(&'static str, u32, &'static str) -> ();
+        serialize_seq(Option<usize>) -> Self::SerializeSeq;
+        serialize_tuple(usize) -> Self::SerializeTuple;
+        serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+        serialize_tuple_variant(&'static str, u32, &'static str, usize) -> Self::SerializeTupleVariant;
+        serialize_map(Option<usize>) -> Self::SerializeMap;
+        serialize_struct_variant(&'static str, u32, &'static str, usize) -> Self::SerializeStructVariant;
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        Err(not_a_struct())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        Err(not_a_struct())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Picks out the field values that fit an integer or string column.
+struct Probe;
+
+#[derive(Debug)]
+struct NotScalar;
+
+impl Display for NotScalar {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("not a scalar")
+    }
+}
+
+impl std::error::Error for NotScalar {}
+
+impl serde::ser::Error for NotScalar {
+    fn custom<T: Display>(_msg: T) -> Self {
+        NotScalar
+    }
+}
+
+macro_rules! probe_int {
+    ($($name:ident($ty:ty) => $kind:ident;)*) => {
+        $(
+            fn $name(self, v: $ty) -> Result<Self::Ok, Self::Error> {
+                Ok(Some(Scalar::Int(IntKind::$kind, v as i128)))
+            }
+        )*
+    };
+}
+
+impl Serializer for Probe {
+    type Ok = Option<Scalar>;
+    type Error = NotScalar;
+    type SerializeSeq = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeTuple = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeTupleStruct = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeTupleVariant = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeMap = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeStruct = Impossible<Option<Scalar>, NotScalar>;
+    type SerializeStructVariant = Impossible<Option<Scalar>, NotScalar>;
+
+    probe_int! {
+        serialize_i8(i8) => I8;
+        serialize_i16(i16) => I16;
+        serialize_i32(i32) => I32;
+        serialize_i64(i64) => I64;
+        serialize_u8(u8) => U8;
+        serialize_u16(u16) => U16;
+        serialize_u32(u32) => U32;
+        serialize_u64(u64) => U64;
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Scalar::Str(v.to_string())))
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        Ok(None)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_newtype_variant<T>(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        Ok(None)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _variant_index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(NotScalar)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct RowDeserializer<'r, 'c> {
+    columns: &'r mut Vec<ColumnReader<'c>>,
+}
+
+impl<'c> Deserializer<'c> for RowDeserializer<'_, 'c> {
+    type Error = SerbfError;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'c>
+    {
+        Err(not_a_struct())
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'c>
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'c>
+    {
+        if fields.len() != self.columns.len() {
+            return Err(SerbfError::Message(format!("Expected {} columns, found {}", fields.len(), self.columns.len())));
+        }
+
+        visitor.visit_seq(RowAccess { columns: self.columns, index: 0 })
+    }
+
+    forward_to_deserialize_any! {
+        <W: Visitor<'c>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct RowAccess<'a, 'c> {
+    columns: &'a mut Vec<ColumnReader<'c>>,
+    index: usize,
+}
+
+impl<'c> SeqAccess<'c> for RowAccess<'_, 'c> {
+    type Error = SerbfError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'c>
+    {
+        let column = match self.columns.get_mut(self.index) {
+            Some(v) => { v }
+            None => { return Ok(None) }
+        };
+
+        self.index += 1;
+
+        column.next(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.columns.len() - self.index)
+    }
+}
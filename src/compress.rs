@@ -0,0 +1,228 @@
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::sync::Arc;
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+/// Compression codec, recorded in the header byte in front of the payload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    None,
+
+    /// LZ4 frame format.
+    #[cfg(feature = "lz4")]
+    Lz4,
+
+    /// Raw deflate stream.
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+impl Algorithm {
+    fn id(&self) -> u8 {
+        match self {
+            Algorithm::None => { 0 }
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => { 1 }
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate => { 2 }
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self, SerbfError> {
+        match id {
+            0 => { Ok(Algorithm::None) }
+            #[cfg(feature = "lz4")]
+            1 => { Ok(Algorithm::Lz4) }
+            #[cfg(feature = "deflate")]
+            2 => { Ok(Algorithm::Deflate) }
+            v => { Err(SerbfError::UnsupportedCompression(v)) }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Compression {
+    pub algorithm: Algorithm,
+
+    /// Payloads smaller than this many bytes are stored uncompressed.
+    pub threshold: usize,
+}
+
+impl Compression {
+    pub const DEFAULT_THRESHOLD: usize = 128;
+
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            threshold: Self::DEFAULT_THRESHOLD,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Writes the header byte and the (possibly) compressed payload.
+    pub fn write<W: Write>(&self, writer: &mut W, data: &[u8]) -> Result<(), SerbfError> {
+        let algorithm = match data.len() < self.threshold {
+            true => { Algorithm::None }
+            false => { self.algorithm }
+        };
+
+        match writer.write_all(&[algorithm.id()]) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        let res = match algorithm {
+            Algorithm::None => { writer.write_all(data) }
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+
+                encoder.write_all(data).and_then(|_| encoder.finish().map(|_| ()).map_err(From::from))
+            }
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate => {
+                let mut encoder = flate2::write::DeflateEncoder::new(writer, flate2::Compression::new(6));
+
+                encoder.write_all(data).and_then(|_| encoder.finish().map(|_| ()))
+            }
+        };
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, SerbfError> {
+        let mut buf = Vec::new();
+
+        self.write(&mut buf, data)?;
+
+        Ok(buf)
+    }
+}
+
+/// Serializer whose output is compressed once it's complete.
+pub struct CompressedSerializer {
+    serializer: SerbfSerializer,
+    compression: Compression,
+}
+
+impl CompressedSerializer {
+    pub fn new(config: SerbfConfig, compression: Compression) -> Self {
+        Self {
+            serializer: SerbfSerializer::with_config(config),
+            compression,
+        }
+    }
+
+    pub fn serializer(&mut self) -> &mut SerbfSerializer {
+        &mut self.serializer
+    }
+
+    pub fn into_inner(self) -> Result<Vec<u8>, SerbfError> {
+        self.compression.compress(&self.serializer.into_inner())
+    }
+
+    /// Streams the compressed output into `writer`.
+    pub fn write_to<W: Write>(self, writer: &mut W) -> Result<(), SerbfError> {
+        self.compression.write(writer, &self.serializer.into_inner())
+    }
+}
+
+/// Decompressed payload that deserializers can borrow from.
+pub struct Decompressed<'a> {
+    data: Cow<'a, [u8]>,
+}
+
+impl<'a> Decompressed<'a> {
+    /// Borrows `data` when it was stored uncompressed.
+    pub fn from_slice(data: &'a [u8]) -> Result<Self, SerbfError> {
+        Self::from_slice_with_limit(data, usize::MAX)
+    }
+
+    /// Fails with `SerbfError::DecompressionLimit` rather than decompressing more than `limit`
+    /// bytes, for payloads that can't be trusted not to be decompression bombs.
+    pub fn from_slice_with_limit(data: &'a [u8], limit: usize) -> Result<Self, SerbfError> {
+        let (id, payload) = match data.split_first() {
+            Some((id, payload)) => { (*id, payload) }
+            None => { return Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof)))) }
+        };
+
+        let data = match Algorithm::from_id(id)? {
+            Algorithm::None if payload.len() > limit => { return Err(SerbfError::DecompressionLimit(limit)) }
+            Algorithm::None => { Cow::Borrowed(payload) }
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => { Cow::Owned(decompress(Algorithm::Lz4, payload, limit)?) }
+            #[cfg(feature = "deflate")]
+            Algorithm::Deflate => { Cow::Owned(decompress(Algorithm::Deflate, payload, limit)?) }
+        };
+
+        Ok(Self {
+            data,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn deserializer(&self, config: SerbfConfig) -> Result<SerbfDeserializer<'_>, SerbfError> {
+        SerbfDeserializer::with_config(&self.data, config)
+    }
+}
+
+impl Decompressed<'static> {
+    /// Reads the header byte and decompresses the rest of `reader`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, SerbfError> {
+        Self::from_reader_with_limit(reader, usize::MAX)
+    }
+
+    /// Like `from_slice_with_limit`, stops reading once the limit is exceeded.
+    pub fn from_reader_with_limit<R: Read>(mut reader: R, limit: usize) -> Result<Self, SerbfError> {
+        let mut id = [0];
+
+        match reader.read_exact(&mut id) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        Ok(Self {
+            data: Cow::Owned(decompress(Algorithm::from_id(id[0])?, reader, limit)?),
+        })
+    }
+}
+
+/// Streams `reader` through the decoder of `algorithm`, reading one byte past `limit` at most
+/// to tell whether it's exceeded.
+fn decompress<R: Read>(algorithm: Algorithm, reader: R, limit: usize) -> Result<Vec<u8>, SerbfError> {
+    let max = (limit as u64).saturating_add(1);
+    let mut data = Vec::new();
+
+    // Errors of the decoders also cover corrupt payloads, not only failed reads
+    let res = match algorithm {
+        Algorithm::None => { reader.take(max).read_to_end(&mut data).map_err(|e| SerbfError::IOError(Arc::new(e))) }
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4 => {
+            lz4_flex::frame::FrameDecoder::new(reader).take(max).read_to_end(&mut data).map_err(|e| SerbfError::Decompression(e.to_string()))
+        }
+        #[cfg(feature = "deflate")]
+        Algorithm::Deflate => {
+            flate2::read::DeflateDecoder::new(reader).take(max).read_to_end(&mut data).map_err(|e| SerbfError::Decompression(e.to_string()))
+        }
+    };
+
+    match res {
+        Ok(_) if data.len() > limit => { Err(SerbfError::DecompressionLimit(limit)) }
+        Ok(_) => { Ok(data) }
+        Err(e) => { Err(e) }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::checksum::Checksum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SerbfConfig {
+    /// Prefix every value with a type tag so it can be decoded without knowing its type.
+    pub self_describing: bool,
+
+    pub endian: Endian,
+
+    pub int_encoding: IntEncoding,
+
+    pub struct_encoding: StructEncoding,
+
+    /// Trailer appended by `SerbfSerializer::into_inner` and verified before decoding.
+    pub checksum: Checksum,
+
+    /// Deterministic output: map entries are sorted by their encoded keys and NaNs are
+    /// normalized. Decoding rejects overlong varints and non-canonical NaNs.
+    pub canonical: bool,
+
+    /// Write repeated strings as a reference to their first occurrence in the same message.
+    /// Ignored in canonical mode, which reorders map entries after they're written. Strings
+    /// skipped without being decoded, like unknown trailing fields, can't be referenced later.
+    /// Decoding a `LazySeq` fails, its elements are decoded on their own and would miss the
+    /// strings they reference.
+    pub intern_strings: bool,
+
+    /// Decoding a `LazySeq` fails, its elements share packed bytes with each other and with the
+    /// values around them.
+    pub bit_packing: BitPacking,
+
+    /// Lay the data out the way another serde format does, see `Compat`.
+    pub compat: Compat,
+
+    /// Ignored in compatibility modes, which use the char encoding of their format.
+    pub char_encoding: CharEncoding,
+
+    /// Decoding rejects varints that aren't minimally encoded, so every value has a single
+    /// valid encoding, like in canonical mode but without constraining the encoder.
+    pub strict: bool,
+}
+
+/// The only NaNs written and accepted in canonical mode.
+pub(crate) const CANONICAL_NAN_F32: u32 = 0x7fc0_0000;
+pub(crate) const CANONICAL_NAN_F64: u64 = 0x7ff8_0000_0000_0000;
+
+/// Byte order of fixed-width integers and floats.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// Encoding of integers wider than a byte.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntEncoding {
+    #[default]
+    Fixed,
+
+    /// LEB128 varints, zigzag encoded for signed integers.
+    Varint,
+}
+
+/// Packing of bools, option tags and variant indices into shared bytes, least significant bit
+/// first. Variant indices take 3 bits, indices from 7 on are followed by a varint of the rest.
+/// Ignored when self-describing or canonical. The body of a length-prefixed struct has bytes of
+/// its own and its length prefix also counts the padding bits of the last one, so fields added
+/// later still take their default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BitPacking {
+    #[default]
+    Off,
+
+    /// Every struct ends the current byte.
+    PerStruct,
+
+    /// Bytes are shared across the whole message.
+    PerMessage,
+}
+
+/// Encoding of chars. Decoding fails with `SerbfError::InvalidChar` unless it finds exactly one
+/// Unicode scalar value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CharEncoding {
+    /// A length-prefixed UTF-8 string.
+    #[default]
+    Str,
+
+    /// The UTF-8 bytes alone, their length is implied by the first one.
+    Utf8,
+
+    /// The scalar value as a u32, following `IntEncoding`.
+    Scalar,
+}
+
+/// Byte-for-byte compatibility with another serde format, for exchanging data with services
+/// that still use it. Only the layout the formats disagree on changes, the other options keep
+/// their meaning, so leave them at their defaults or use the matching preset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compat {
+    #[default]
+    Off,
+
+    /// bincode v1 with its default options, see `SerbfConfig::bincode`. Lengths are u64,
+    /// variant indices u32, both fixed-width in the configured byte order. Chars are their UTF-8
+    /// bytes without a length and tuples have no length prefix.
+    Bincode,
+
+    /// postcard, see `SerbfConfig::postcard`. Its layout is the varint one except that tuples
+    /// have no length prefix.
+    Postcard,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StructEncoding {
+    /// Fields are written back to back, decoding requires the exact same struct definition.
+    #[default]
+    Sequence,
+
+    /// The fields are prefixed with their byte length. Fields missing at the end take their
+    /// `#[serde(default)]` value and unknown trailing fields are skipped, so structs can grow.
+    LengthPrefixed,
+}
+
+impl SerbfConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn self_describing() -> Self {
+        Self {
+            self_describing: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn canonical() -> Self {
+        Self {
+            canonical: true,
+            ..Self::default()
+        }
+    }
+
+    /// The layout of `bincode::serialize`: little-endian fixed-width integers and u64 lengths.
+    pub fn bincode() -> Self {
+        Self {
+            compat: Compat::Bincode,
+            ..Self::default()
+        }
+    }
+
+    /// The layout of `postcard::to_stdvec`: varint integers, zigzag encoded when signed, and
+    /// little-endian floats.
+    pub fn postcard() -> Self {
+        Self {
+            int_encoding: IntEncoding::Varint,
+            compat: Compat::Postcard,
+            ..Self::default()
+        }
+    }
+
+    /// Tuples of a known length are prefixed with it unless a compatible format omits it.
+    /// Decoding tuples without a type hint needs their length, so it's kept when self-describing.
+    pub(crate) fn tuple_lengths(&self) -> bool {
+        self.compat == Compat::Off || self.self_describing
+    }
+
+    pub(crate) fn char_encoding(&self) -> CharEncoding {
+        match self.compat {
+            Compat::Off => { self.char_encoding }
+            Compat::Bincode => { CharEncoding::Utf8 }
+            Compat::Postcard => { CharEncoding::Str }
+        }
+    }
+
+    pub(crate) fn packs_bits(&self) -> bool {
+        self.bit_packing != BitPacking::Off && !self.self_describing && !self.canonical
+    }
+
+    pub(crate) fn interns_strings(&self) -> bool {
+        self.intern_strings && !self.canonical
+    }
+}
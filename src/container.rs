@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::Arc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::envelope::Envelope;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+const FOOTER_MAGIC: [u8; 4] = *b"SRBX";
+const FOOTER_LEN: u64 = 12;
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    /// Canonical encoding of the record's key
+    key: Option<Vec<u8>>,
+    offset: u64,
+    len: u64,
+}
+
+fn encode_key<K>(key: &K) -> Result<Vec<u8>, SerbfError>
+where
+    K: ?Sized + Serialize
+{
+    let mut serializer = SerbfSerializer::with_config(SerbfConfig::canonical());
+    key.serialize(&mut serializer)?;
+
+    Ok(serializer.into_inner())
+}
+
+/// Writes an immutable container of individually encoded records.
+///
+/// Layout: an envelope header, the records, an index of every record's optional key, offset
+/// and length, and a footer with the index offset (u64, little-endian) and `SRBX`.
+pub struct ContainerWriter<W> {
+    writer: W,
+    config: SerbfConfig,
+    position: u64,
+    entries: Vec<IndexEntry>,
+    keys: BTreeMap<Vec<u8>, usize>,
+}
+
+impl<W: Write> ContainerWriter<W> {
+    pub fn new(writer: W, config: SerbfConfig) -> Result<Self, SerbfError> {
+        Self::with_envelope(writer, &Envelope::new(config))
+    }
+
+    pub fn with_envelope(writer: W, envelope: &Envelope) -> Result<Self, SerbfError> {
+        let mut header = Vec::new();
+        envelope.write(&mut header)?;
+
+        let mut container = Self {
+            writer,
+            config: envelope.config,
+            position: 0,
+            entries: Vec::new(),
+            keys: BTreeMap::new(),
+        };
+
+        container.write_all(&header)?;
+
+        Ok(container)
+    }
+
+    /// Appends a record and returns its ordinal.
+    pub fn append<T>(&mut self, value: &T) -> Result<usize, SerbfError>
+    where
+        T: ?Sized + Serialize
+    {
+        self.write_record(None, value)
+    }
+
+    /// Appends a record that can also be looked up by `key`.
+    pub fn insert<K, T>(&mut self, key: &K, value: &T) -> Result<usize, SerbfError>
+    where
+        K: ?Sized + Serialize,
+        T: ?Sized + Serialize
+    {
+        let key = encode_key(key)?;
+
+        if self.keys.contains_key(&key) {
+            return Err(SerbfError::DuplicateKey);
+        }
+
+        self.write_record(Some(key), value)
+    }
+
+    /// Writes the index and the footer and returns the writer.
+    pub fn finish(mut self) -> Result<W, SerbfError> {
+        let index_offset = self.position;
+
+        let mut serializer = SerbfSerializer::with_config(self.config);
+        self.entries.serialize(&mut serializer)?;
+
+        self.write_all(&serializer.into_inner())?;
+        self.write_all(&index_offset.to_le_bytes())?;
+        self.write_all(&FOOTER_MAGIC)?;
+
+        match self.writer.flush() {
+            Ok(_) => { Ok(self.writer) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    fn write_record<T>(&mut self, key: Option<Vec<u8>>, value: &T) -> Result<usize, SerbfError>
+    where
+        T: ?Sized + Serialize
+    {
+        let mut serializer = SerbfSerializer::with_config(self.config);
+        value.serialize(&mut serializer)?;
+
+        let record = serializer.into_inner();
+        let offset = self.position;
+
+        self.write_all(&record)?;
+
+        let ordinal = self.entries.len();
+
+        if let Some(key) = &key {
+            self.keys.insert(key.clone(), ordinal);
+        }
+
+        self.entries.push(IndexEntry {
+            key,
+            offset,
+            len: record.len() as u64,
+        });
+
+        Ok(ordinal)
+    }
+
+    fn write_all(&mut self, data: &[u8]) -> Result<(), SerbfError> {
+        match self.writer.write_all(data) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        self.position += data.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Looks up single records of a container, reading only the index and the records asked for.
+pub struct ContainerReader<R> {
+    reader: R,
+    envelope: Envelope,
+    entries: Vec<IndexEntry>,
+    keys: BTreeMap<Vec<u8>, usize>,
+    buf: Vec<u8>,
+}
+
+impl<R: Read + Seek> ContainerReader<R> {
+    pub fn open(mut reader: R) -> Result<Self, SerbfError> {
+        let envelope = match reader.seek(SeekFrom::Start(0)) {
+            Ok(_) => { Envelope::read(&mut reader)? }
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        };
+
+        let mut footer = [0; FOOTER_LEN as usize];
+
+        let footer_offset = match reader.seek(SeekFrom::End(-(FOOTER_LEN as i64))) {
+            Ok(v) => { v }
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        };
+
+        match reader.read_exact(&mut footer) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        if footer[8..] != FOOTER_MAGIC {
+            return Err(SerbfError::InvalidMagic);
+        }
+
+        let mut index_offset = [0; 8];
+        index_offset.copy_from_slice(&footer[..8]);
+        let index_offset = u64::from_le_bytes(index_offset);
+
+        if index_offset > footer_offset {
+            return Err(SerbfError::TruncatedRecord);
+        }
+
+        let mut container = Self {
+            reader,
+            envelope,
+            entries: Vec::new(),
+            keys: BTreeMap::new(),
+            buf: Vec::new(),
+        };
+
+        container.read_at(index_offset, footer_offset - index_offset)?;
+
+        let mut deserializer = SerbfDeserializer::with_config(&container.buf, envelope.config)?;
+        container.entries = Vec::deserialize(&mut deserializer)?;
+
+        for (ordinal, entry) in container.entries.iter().enumerate() {
+            if let Some(key) = &entry.key {
+                container.keys.insert(key.clone(), ordinal);
+            }
+        }
+
+        Ok(container)
+    }
+
+    pub fn envelope(&self) -> &Envelope {
+        &self.envelope
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Ordinal of the record stored under `key`.
+    pub fn position<K>(&self, key: &K) -> Result<Option<usize>, SerbfError>
+    where
+        K: ?Sized + Serialize
+    {
+        Ok(self.keys.get(&encode_key(key)?).copied())
+    }
+
+    /// The encoded record, to be decoded with the envelope's config.
+    pub fn get_raw(&mut self, ordinal: usize) -> Result<Option<&[u8]>, SerbfError> {
+        let (offset, len) = match self.entries.get(ordinal) {
+            Some(v) => { (v.offset, v.len) }
+            None => { return Ok(None) }
+        };
+
+        self.read_at(offset, len)?;
+
+        Ok(Some(&self.buf))
+    }
+
+    pub fn get<T>(&mut self, ordinal: usize) -> Result<Option<T>, SerbfError>
+    where
+        T: DeserializeOwned
+    {
+        let config = self.envelope.config;
+
+        let record = match self.get_raw(ordinal)? {
+            Some(v) => { v }
+            None => { return Ok(None) }
+        };
+
+        let mut deserializer = SerbfDeserializer::with_config(record, config)?;
+
+        Ok(Some(T::deserialize(&mut deserializer)?))
+    }
+
+    pub fn get_by_key<K, T>(&mut self, key: &K) -> Result<Option<T>, SerbfError>
+    where
+        K: ?Sized + Serialize,
+        T: DeserializeOwned
+    {
+        match self.position(key)? {
+            Some(v) => { self.get(v) }
+            None => { Ok(None) }
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn read_at(&mut self, offset: u64, len: u64) -> Result<(), SerbfError> {
+        match self.reader.seek(SeekFrom::Start(offset)) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        self.buf.clear();
+
+        match (&mut self.reader).take(len).read_to_end(&mut self.buf) {
+            Ok(v) if (v as u64) < len => { Err(SerbfError::TruncatedRecord) }
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+}
@@ -1,317 +1,850 @@
-use std::char::DecodeUtf16;
-use std::io::{Cursor, Error, Read};
-use std::ptr::read;
-use std::slice::Iter;
-use std::string::FromUtf8Error;
+use std::io::{Cursor, Error, ErrorKind, Read};
 use std::sync::Arc;
-use byteorder::{LittleEndian, ReadBytesExt};
-use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
-use serde::{Deserialize, Deserializer};
-use varint_rs::VarintReader;
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use serde::de::{DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::Deserializer;
+use crate::columnar;
+use crate::config::{BitPacking, CharEncoding, Compat, Endian, IntEncoding, SerbfConfig, StructEncoding, CANONICAL_NAN_F32, CANONICAL_NAN_F64};
+use crate::envelope::Envelope;
 use crate::error::SerbfError;
-use crate::ser::SerbfSerializer;
+use crate::lazy;
+use crate::tag::Tag;
+
+/// Deepest nesting decoded before failing with `SerbfError::RecursionLimit`.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// Reads a LEB128 varint of at most `bits` bits. Fails with `SerbfError::VarintOverflow` as
+/// soon as it runs past them and, if `minimal`, with `SerbfError::NonCanonicalVarint` when
+/// it's longer than it has to be.
+fn read_uvarint<R: Read>(reader: &mut R, bits: u32, minimal: bool) -> Result<u128, SerbfError> {
+    let mut value = 0u128;
+    let mut shift = 0;
+
+    loop {
+        let byte = match reader.read_u8() {
+            Ok(v) => { v }
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        };
+
+        if shift >= bits || (bits - shift < 7 && (byte & 0x7f) >> (bits - shift) != 0) {
+            return Err(SerbfError::VarintOverflow);
+        }
+
+        value |= ((byte & 0x7f) as u128) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            // A minimal varint never ends in a zero byte, unless it's only that byte
+            if minimal && shift > 7 && byte == 0 {
+                return Err(SerbfError::NonCanonicalVarint);
+            }
+
+            return Ok(value);
+        }
+    }
+}
+
+/// Bounded varint decoding for everything read outside of `SerbfDeserializer` too.
+pub(crate) fn read_varint<T: TryFrom<u128>, R: Read>(reader: &mut R, minimal: bool) -> Result<T, SerbfError> {
+    let value = read_uvarint(reader, std::mem::size_of::<T>() as u32 * 8, minimal)?;
+
+    match T::try_from(value) {
+        Ok(v) => { Ok(v) }
+        Err(_) => { Err(SerbfError::VarintOverflow) }
+    }
+}
+
+pub(crate) fn read_zigzag<T: TryFrom<i128>, R: Read>(reader: &mut R, minimal: bool) -> Result<T, SerbfError> {
+    let value = read_uvarint(reader, std::mem::size_of::<T>() as u32 * 8, minimal)?;
+
+    match T::try_from((value >> 1) as i128 ^ -((value & 1) as i128)) {
+        Ok(v) => { Ok(v) }
+        Err(_) => { Err(SerbfError::VarintOverflow) }
+    }
+}
+
+/// Converts a length read off the wire, generic over the target so narrower `usize`s can be
+/// tested on any host.
+pub(crate) fn narrow_len<T: TryFrom<u64>>(len: u64) -> Result<T, SerbfError> {
+    match T::try_from(len) {
+        Ok(v) => { Ok(v) }
+        Err(_) => { Err(SerbfError::LengthOverflow(len)) }
+    }
+}
 
 #[derive(Clone)]
 pub struct SerbfDeserializer<'a> {
     buf: Cursor<&'a [u8]>,
+    config: SerbfConfig,
+
+    /// Strings read so far, when interning
+    strings: Vec<&'a str>,
+
+    /// Unread bits of the current bit-packed byte, least significant first
+    bits: u8,
+    bits_left: u8,
+
+    /// Padding bits of the last packed byte of the length-prefixed struct being read
+    bits_padding: u8,
+
+    /// Bit-packing state of the structs around the length-prefixed ones being read
+    outer_bits: Vec<(u8, u8, u8)>,
+
+    /// Options, sequences, maps, structs and variants currently being decoded
+    depth: usize,
 }
 
 impl<'a> SerbfDeserializer<'a> {
     pub fn new(data: &'a [u8]) -> Self {
+        Self::with_config_unchecked(data, SerbfConfig::default())
+    }
+
+    /// Fails if the config has a checksum and the data's trailer doesn't match.
+    pub fn with_config(data: &'a [u8], config: SerbfConfig) -> Result<Self, SerbfError> {
+        Ok(Self::with_config_unchecked(config.checksum.verify(data)?, config))
+    }
+
+    /// Reads and validates the envelope header, then decodes the payload with its config.
+    pub fn with_envelope(data: &'a [u8]) -> Result<(Self, Envelope), SerbfError> {
+        let mut header = Cursor::new(data);
+
+        let envelope = Envelope::read(&mut header)?;
+
+        // The checksum covers the header as well
+        let mut deserializer = Self::with_config_unchecked(envelope.config.checksum.verify(data)?, envelope.config);
+        deserializer.buf.set_position(header.position());
+
+        Ok((deserializer, envelope))
+    }
+
+    pub fn config(&self) -> &SerbfConfig {
+        &self.config
+    }
+
+    /// Fails unless the whole input has been decoded, to reject data after the last value.
+    pub fn end(&self) -> Result<(), SerbfError> {
+        match self.data().len().saturating_sub(self.position()) {
+            0 => { Ok(()) }
+            v => { Err(SerbfError::TrailingData(v)) }
+        }
+    }
+
+    /// Decodes `data` as is, for slices cut out of an already verified message.
+    pub(crate) fn with_config_unchecked(data: &'a [u8], config: SerbfConfig) -> Self {
         Self {
             buf: Cursor::new(data),
+            config,
+            strings: Vec::new(),
+            bits: 0,
+            bits_left: 0,
+            bits_padding: 0,
+            outer_bits: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Goes one nesting level deeper. Fails past `MAX_DEPTH`, so crafted input can't overflow
+    /// the stack, every successful call has to be matched by a `leave`.
+    pub(crate) fn enter(&mut self) -> Result<(), SerbfError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(SerbfError::RecursionLimit(MAX_DEPTH));
+        }
+
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn nested<T, F>(&mut self, f: F) -> Result<T, SerbfError>
+    where
+        F: FnOnce(&mut Self) -> Result<T, SerbfError>
+    {
+        self.enter()?;
+        let res = f(self);
+        self.leave();
+
+        res
+    }
+
+    pub(crate) fn data(&self) -> &'a [u8] {
+        self.buf.get_ref()
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.buf.position() as usize
+    }
+
+    pub(crate) fn read_tag(&mut self) -> Result<Tag, SerbfError> {
+        match self.buf.read_u8() {
+            Ok(v) => { Tag::try_from(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn expect_tag(&mut self, expected: Tag) -> Result<(), SerbfError> {
+        if !self.config.self_describing {
+            return Ok(());
+        }
+
+        let tag = self.read_tag()?;
+
+        match tag == expected {
+            true => { Ok(()) }
+            false => { Err(SerbfError::InvalidTag(tag as u8)) }
+        }
+    }
+
+    /// Reads a varint of at most `bits` bits. Varints that don't fit are rejected, as are ones
+    /// that aren't minimally encoded in canonical and strict mode.
+    fn read_varint<T: TryFrom<u128>>(&mut self) -> Result<T, SerbfError> {
+        let minimal = self.config.canonical || self.config.strict;
+
+        read_varint(&mut self.buf, minimal)
+    }
+
+    fn read_zigzag<T: TryFrom<i128>>(&mut self) -> Result<T, SerbfError> {
+        let minimal = self.config.canonical || self.config.strict;
+
+        read_zigzag(&mut self.buf, minimal)
+    }
+
+    /// Lengths are u64 on the wire, whatever the width of `usize` on either side.
+    pub(crate) fn read_len(&mut self) -> Result<usize, SerbfError> {
+        let res = match (self.config.compat, self.config.endian) {
+            (Compat::Off | Compat::Postcard, _) => { Ok(self.read_varint::<u64>()?) }
+            (Compat::Bincode, Endian::Little) => { self.buf.read_u64::<LittleEndian>() }
+            (Compat::Bincode, Endian::Big) => { self.buf.read_u64::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { narrow_len(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    /// Reads the length of a tuple expected to have `len` elements, if it's written.
+    pub(crate) fn read_tuple_len(&mut self, len: usize) -> Result<usize, SerbfError> {
+        match self.config.tuple_lengths() {
+            true => { self.read_len() }
+            false => { Ok(len) }
+        }
+    }
+
+    pub(crate) fn read_slice(&mut self, len: usize) -> Result<&'a [u8], SerbfError> {
+        let data: &'a [u8] = self.buf.get_ref();
+        let start = self.buf.position() as usize;
+
+        let end = match start.checked_add(len) {
+            Some(v) if v <= data.len() => { v }
+            _ => { return Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof)))) }
+        };
+
+        self.buf.set_position(end as u64);
+
+        Ok(&data[start..end])
+    }
+
+    /// Reads the byte length of a struct body and returns where it ends, if structs are length-prefixed.
+    pub(crate) fn read_struct_end(&mut self) -> Result<Option<u64>, SerbfError> {
+        self.end_struct_bits();
+
+        if self.config.self_describing || self.config.struct_encoding != StructEncoding::LengthPrefixed {
+            return Ok(None);
+        }
+
+        let mut len = self.read_len()?;
+
+        // The body packs its bits into bytes of its own, see `SerbfSerializer::end_struct`
+        if self.config.packs_bits() {
+            self.outer_bits.push((self.bits, self.bits_left, self.bits_padding));
+            self.bits_left = 0;
+            self.bits_padding = (len & 7) as u8;
+            len >>= 3;
+        }
+
+        match (self.buf.position() as usize).checked_add(len) {
+            Some(v) if v <= self.buf.get_ref().len() => { Ok(Some(v as u64)) }
+            _ => { Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof)))) }
+        }
+    }
+
+    /// Whether the length-prefixed struct ending at `end` has unread fields. Once its bytes are
+    /// read, the current packed byte is the body's last one and only its bits before the padding
+    /// are fields.
+    pub(crate) fn has_struct_fields(&self, end: Option<u64>) -> bool {
+        match end {
+            Some(v) => { self.buf.position() < v || self.bits_left > self.bits_padding }
+            None => { true }
+        }
+    }
+
+    /// Skips the unknown trailing fields of a length-prefixed struct.
+    pub(crate) fn finish_struct(&mut self, end: Option<u64>) -> Result<(), SerbfError> {
+        self.end_struct_bits();
+
+        let end = match end {
+            Some(v) => { v }
+            None => { return Ok(()) }
+        };
+
+        if self.buf.position() > end {
+            return Err(SerbfError::StructOverrun);
+        }
+
+        self.buf.set_position(end);
+
+        if let Some((bits, bits_left, bits_padding)) = self.outer_bits.pop() {
+            self.bits = bits;
+            self.bits_left = bits_left;
+            self.bits_padding = bits_padding;
+        }
+
+        Ok(())
+    }
+
+    /// Drops what's left of the current bit-packed byte when every struct gets its own.
+    fn end_struct_bits(&mut self) {
+        if self.config.bit_packing == BitPacking::PerStruct {
+            self.bits_left = 0;
+        }
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u32, SerbfError> {
+        let mut value = 0;
+
+        for i in 0..count {
+            if self.bits_left == 0 {
+                self.bits = match self.buf.read_u8() {
+                    Ok(v) => { v }
+                    Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+                };
+                self.bits_left = 8;
+            }
+
+            value |= ((self.bits & 1) as u32) << i;
+
+            self.bits >>= 1;
+            self.bits_left -= 1;
+        }
+
+        Ok(value)
+    }
+
+    fn read_utf8(&mut self, len: usize) -> Result<&'a str, SerbfError> {
+        let slice = self.read_slice(len)?;
+
+        match std::str::from_utf8(slice) {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::UTF8StrError(e)) }
+        }
+    }
+
+    pub(crate) fn read_str(&mut self) -> Result<&'a str, SerbfError> {
+        let len = self.read_len()?;
+
+        if !self.config.interns_strings() {
+            return self.read_utf8(len);
+        }
+
+        if len & 1 == 1 {
+            return match self.strings.get(len >> 1) {
+                Some(v) => { Ok(*v) }
+                None => { Err(SerbfError::InvalidStringRef(len >> 1)) }
+            };
+        }
+
+        let v = self.read_utf8(len >> 1)?;
+        self.strings.push(v);
+
+        Ok(v)
+    }
+
+    pub(crate) fn read_bool(&mut self) -> Result<bool, SerbfError> {
+        if self.config.packs_bits() {
+            return Ok(self.read_bits(1)? == 1);
+        }
+
+        match self.buf.read_u8() {
+            Ok(0) => { Ok(false) }
+            Ok(1) => { Ok(true) }
+            Ok(v) => { Err(SerbfError::InvalidBool(v)) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    /// Reads whether an option is `Some`, in compact mode.
+    pub(crate) fn read_option_tag(&mut self) -> Result<bool, SerbfError> {
+        match self.read_bool() {
+            Err(SerbfError::InvalidBool(v)) => { Err(SerbfError::InvalidOptionTag(v)) }
+            res => { res }
+        }
+    }
+
+    pub(crate) fn read_char(&mut self) -> Result<char, SerbfError> {
+        let len = match self.config.char_encoding() {
+            CharEncoding::Str => { self.read_len()? }
+            CharEncoding::Utf8 => { utf8_len(self.buf.get_ref().get(self.position()).copied())? }
+            CharEncoding::Scalar => {
+                return match char::from_u32(self.read_u32()?) {
+                    Some(v) => { Ok(v) }
+                    None => { Err(SerbfError::InvalidChar) }
+                };
+            }
+        };
+
+        let mut chars = match std::str::from_utf8(self.read_slice(len)?) {
+            Ok(v) => { v.chars() }
+            Err(_) => { return Err(SerbfError::InvalidChar) }
+        };
+
+        match (chars.next(), chars.next()) {
+            (Some(v), None) => { Ok(v) }
+            _ => { Err(SerbfError::InvalidChar) }
+        }
+    }
+
+    pub(crate) fn read_i8(&mut self) -> Result<i8, SerbfError> {
+        match self.buf.read_i8() {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_i16(&mut self) -> Result<i16, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_zigzag() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_i16::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_i16::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_i32(&mut self) -> Result<i32, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_zigzag() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_i32::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_i32::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_i64(&mut self) -> Result<i64, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_zigzag() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_i64::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_i64::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_i128(&mut self) -> Result<i128, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_zigzag() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_i128::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_i128::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, SerbfError> {
+        match self.buf.read_u8() {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_varint() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_u16::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_u16::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_varint() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_u32::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_u32::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_varint() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_u64::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_u64::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_u128(&mut self) -> Result<u128, SerbfError> {
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { return self.read_varint() }
+            (IntEncoding::Fixed, Endian::Little) => { self.buf.read_u128::<LittleEndian>() }
+            (IntEncoding::Fixed, Endian::Big) => { self.buf.read_u128::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_f32(&mut self) -> Result<f32, SerbfError> {
+        let res = match self.config.endian {
+            Endian::Little => { self.buf.read_f32::<LittleEndian>() }
+            Endian::Big => { self.buf.read_f32::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) if self.config.canonical && v.is_nan() && v.to_bits() != CANONICAL_NAN_F32 => { Err(SerbfError::NonCanonicalNaN) }
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
+
+    pub(crate) fn read_f64(&mut self) -> Result<f64, SerbfError> {
+        let res = match self.config.endian {
+            Endian::Little => { self.buf.read_f64::<LittleEndian>() }
+            Endian::Big => { self.buf.read_f64::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) if self.config.canonical && v.is_nan() && v.to_bits() != CANONICAL_NAN_F64 => { Err(SerbfError::NonCanonicalNaN) }
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub(crate) fn read_variant_index(&mut self) -> Result<u32, SerbfError> {
+        if !self.config.packs_bits() {
+            return self.read_variant_index_bytes();
+        }
+
+        match self.read_bits(3)? {
+            7 => {
+                match self.read_variant_index_bytes()?.checked_add(7) {
+                    Some(v) => { Ok(v) }
+                    None => { Err(SerbfError::InvalidEnumID) }
+                }
+            }
+            v => { Ok(v) }
+        }
+    }
+
+    fn read_variant_index_bytes(&mut self) -> Result<u32, SerbfError> {
+        let res = match (self.config.compat, self.config.endian) {
+            (Compat::Off | Compat::Postcard, _) => { return self.read_varint() }
+            (Compat::Bincode, Endian::Little) => { self.buf.read_u32::<LittleEndian>() }
+            (Compat::Bincode, Endian::Big) => { self.buf.read_u32::<BigEndian>() }
+        };
+
+        match res {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+}
+
+/// Byte length of the UTF-8 char starting with `lead`.
+fn utf8_len(lead: Option<u8>) -> Result<usize, SerbfError> {
+    match lead {
+        Some(0x00..=0x7f) => { Ok(1) }
+        Some(0xc2..=0xdf) => { Ok(2) }
+        Some(0xe0..=0xef) => { Ok(3) }
+        Some(0xf0..=0xf4) => { Ok(4) }
+        Some(_) => { Err(SerbfError::InvalidChar) }
+        None => { Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof)))) }
+    }
 }
 
-impl<'de, 'a> Deserializer<'de> for &'a mut SerbfDeserializer<'de> {
+impl<'de> Deserializer<'de> for &mut SerbfDeserializer<'de> {
     type Error = SerbfError;
 
-    fn deserialize_any<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        todo!()
+        if !self.config.self_describing {
+            return Err(SerbfError::AnyNotSupported);
+        }
+
+        match self.read_tag()? {
+            Tag::Unit => { visitor.visit_unit() }
+            Tag::Bool => { visitor.visit_bool(self.read_bool()?) }
+            Tag::I8 => { visitor.visit_i8(self.read_i8()?) }
+            Tag::I16 => { visitor.visit_i16(self.read_i16()?) }
+            Tag::I32 => { visitor.visit_i32(self.read_i32()?) }
+            Tag::I64 => { visitor.visit_i64(self.read_i64()?) }
+            Tag::I128 => { visitor.visit_i128(self.read_i128()?) }
+            Tag::U8 => { visitor.visit_u8(self.read_u8()?) }
+            Tag::U16 => { visitor.visit_u16(self.read_u16()?) }
+            Tag::U32 => { visitor.visit_u32(self.read_u32()?) }
+            Tag::U64 => { visitor.visit_u64(self.read_u64()?) }
+            Tag::U128 => { visitor.visit_u128(self.read_u128()?) }
+            Tag::F32 => { visitor.visit_f32(self.read_f32()?) }
+            Tag::F64 => { visitor.visit_f64(self.read_f64()?) }
+            Tag::Char => { visitor.visit_char(self.read_char()?) }
+            Tag::Str => { visitor.visit_borrowed_str(self.read_str()?) }
+            Tag::Bytes => {
+                let len = self.read_len()?;
+
+                visitor.visit_borrowed_bytes(self.read_slice(len)?)
+            }
+            Tag::None => { visitor.visit_none() }
+            Tag::Some => { self.nested(|de| visitor.visit_some(de)) }
+            Tag::Seq => {
+                let len = self.read_len()?;
+
+                self.nested(|de| visitor.visit_seq(SerbfSeperatedDeserializer::new(de, len)))
+            }
+            Tag::Map => {
+                let len = self.read_len()?;
+
+                self.nested(|de| visitor.visit_map(SerbfSeperatedDeserializer::new(de, len)))
+            }
+            Tag::Variant => { self.nested(|de| visitor.visit_enum(de)) }
+        }
     }
 
     fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u8() {
-            Ok(0) => { false }
-            Ok(1) => { true }
-            Ok(_) => { return Err(SerbfError::InvalidEnumID) }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_bool(v)
+        visitor.visit_bool(self.read_bool()?)
     }
 
     fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_i8() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_i8(v)
+        visitor.visit_i8(self.read_i8()?)
     }
 
     fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_i16::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_i16(v)
+        visitor.visit_i16(self.read_i16()?)
     }
 
     fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_i32::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_i32(v)
+        visitor.visit_i32(self.read_i32()?)
     }
 
     fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_i64::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_i64(v)
+        visitor.visit_i64(self.read_i64()?)
     }
 
     fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_i128::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_i128(v)
+        visitor.visit_i128(self.read_i128()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u8() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_u8(v)
+        visitor.visit_u8(self.read_u8()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u16::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_u16(v)
+        visitor.visit_u16(self.read_u16()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u32::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_u32(v)
+        visitor.visit_u32(self.read_u32()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u64::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_u64(v)
+        visitor.visit_u64(self.read_u64()?)
     }
 
     fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_u128::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_u128(v)
+        visitor.visit_u128(self.read_u128()?)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_f32::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_f32(v)
+        visitor.visit_f32(self.read_f32()?)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let v = match self.buf.read_f64::<LittleEndian>() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_f64(v)
+        visitor.visit_f64(self.read_f64()?)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        let mut vec = vec![0; len];
-
-        match self.buf.read_exact(vec.as_mut_slice()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        let str = match String::from_utf8(vec) {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::UTF8Error(e)) }
-        };
-
-        match str.chars().nth(0) {
-            Some(v) => { visitor.visit_char(v) }
-            None => { Err(SerbfError::InvalidChar) }
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
         }
+
+        visitor.visit_char(self.read_char()?)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-            let len = match self.buf.read_usize_varint() {
-                Ok(v) => { v }
-                Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-            };
-
-            let mut vec = vec![0; len];
-
-            match self.buf.read_exact(vec.as_mut_slice()) {
-                Ok(_) => {}
-                Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-            };
-
-            let string = match String::from_utf8(vec) {
-                Ok(v) => { v }
-                Err(e) => { return Err(SerbfError::UTF8Error(e)) }
-            };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-            visitor.visit_str(string.as_str())
+        visitor.visit_borrowed_str(self.read_str()?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        let mut vec = vec![0; len];
-
-        match self.buf.read_exact(vec.as_mut_slice()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        match String::from_utf8(vec) {
-            Ok(v) => { visitor.visit_string(v) }
-            Err(e) => { Err(SerbfError::UTF8Error(e)) }
-        }
+        self.deserialize_str(visitor)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        let mut vec = vec![0; len];
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        match self.buf.read_exact(vec.as_mut_slice()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        let len = self.read_len()?;
 
-        visitor.visit_bytes(vec.as_slice())
+        visitor.visit_borrowed_bytes(self.read_slice(len)?)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        let mut vec = vec![0; len];
-
-        match self.buf.read_exact(vec.as_mut_slice()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        visitor.visit_byte_buf(vec)
+        self.deserialize_bytes(visitor)
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        match self.buf.read_u8() {
-            Ok(0) => { visitor.visit_some(self) }
-            Ok(1) => { visitor.visit_none() }
-            Ok(_) => { Err(SerbfError::InvalidEnumID) }
-            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        match self.read_option_tag()? {
+            false => { visitor.visit_none() }
+            true => { self.nested(|de| visitor.visit_some(de)) }
         }
     }
 
@@ -319,12 +852,16 @@ impl<'de, 'a> Deserializer<'de> for &'a mut SerbfDeserializer<'de> {
     where
         V: Visitor<'de>
     {
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
         // ¯\_(:/)_/¯ nothing to do
 
         visitor.visit_unit()
     }
 
-    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
@@ -335,78 +872,109 @@ impl<'de, 'a> Deserializer<'de> for &'a mut SerbfDeserializer<'de> {
     where
         V: Visitor<'de>
     {
+        if name == lazy::TOKEN {
+            return lazy::visit_lazy_seq(self, visitor);
+        }
+
+        if name == columnar::TOKEN {
+            let config = self.config;
+
+            return columnar::with_config(config, || visitor.visit_newtype_struct(self));
+        }
+
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(mut self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        let len = self.read_len()?;
 
-        visitor.visit_seq(SerbfSeperatedDeserializer::new(self, len))
+        self.nested(|de| visitor.visit_seq(SerbfSeperatedDeserializer::new(de, len)))
     }
 
-    fn deserialize_tuple<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
 
-        visitor.visit_seq(&mut SerbfSeperatedDeserializer::new(self, len))
+        let len = self.read_tuple_len(len)?;
+
+        self.nested(|de| visitor.visit_seq(SerbfSeperatedDeserializer::new(de, len)))
     }
 
-    fn deserialize_tuple_struct<V>(mut self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        let len = match self.buf.read_usize_varint() {
-            Ok(v) => { v }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        visitor.visit_seq(&mut SerbfSeperatedDeserializer::new(self, len))
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        todo!()
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        let len = self.read_len()?;
+
+        self.nested(|de| visitor.visit_map(SerbfSeperatedDeserializer::new(de, len)))
     }
 
-    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        visitor.visit_seq(&mut SerbfSeperatedDeserializer::new(self, fields.len()))
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        let end = self.read_struct_end()?;
+
+        let value = self.nested(|de| visitor.visit_seq(SerbfSeperatedDeserializer::with_end(de, fields.len(), end)))?;
+
+        self.finish_struct(end)?;
+
+        Ok(value)
     }
 
-    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        todo!()
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        self.nested(|de| visitor.visit_enum(de))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        todo!()
+        if self.config.self_describing {
+            return self.deserialize_any(visitor);
+        }
+
+        visitor.visit_u32(self.read_variant_index()?)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>
     {
-        todo!()
+        self.deserialize_any(visitor)
     }
 
     fn is_human_readable(&self) -> bool {
@@ -414,24 +982,77 @@ impl<'de, 'a> Deserializer<'de> for &'a mut SerbfDeserializer<'de> {
     }
 }
 
+impl<'de> EnumAccess<'de> for &mut SerbfDeserializer<'de> {
+    type Error = SerbfError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>
+    {
+        let index = self.read_variant_index()?;
+
+        let value = seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(index))?;
+
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut SerbfDeserializer<'de> {
+    type Error = SerbfError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.expect_tag(Tag::Unit)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_struct("", fields, visitor)
+    }
+}
+
 struct SerbfSeperatedDeserializer<'a, 'de: 'a> {
     serbf_deserializer: &'a mut SerbfDeserializer<'de>,
 
     len: usize,
     current_index: usize,
+
+    /// End of a length-prefixed struct body, elements past it are reported as missing
+    end: Option<u64>,
 }
 
 impl<'a, 'de> SerbfSeperatedDeserializer<'a, 'de> {
     pub fn new(serbf_deserializer: &'a mut SerbfDeserializer<'de>, len: usize) -> Self {
+        Self::with_end(serbf_deserializer, len, None)
+    }
+
+    pub fn with_end(serbf_deserializer: &'a mut SerbfDeserializer<'de>, len: usize, end: Option<u64>) -> Self {
         Self {
             serbf_deserializer,
             len,
             current_index: 0,
+            end,
         }
     }
 }
 
-impl<'de, 'a> SeqAccess<'de> for SerbfSeperatedDeserializer<'a, 'de> {
+impl<'de> SeqAccess<'de> for SerbfSeperatedDeserializer<'_, 'de> {
     type Error = SerbfError;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
@@ -440,7 +1061,7 @@ impl<'de, 'a> SeqAccess<'de> for SerbfSeperatedDeserializer<'a, 'de> {
     {
         self.current_index += 1;
 
-        match self.current_index > self.len {
+        match self.current_index > self.len || !self.serbf_deserializer.has_struct_fields(self.end) {
             false => { seed.deserialize(&mut *self.serbf_deserializer).map(Some) }
             true => { Ok(None) }
         }
@@ -451,9 +1072,7 @@ impl<'de, 'a> SeqAccess<'de> for SerbfSeperatedDeserializer<'a, 'de> {
     }
 }
 
-
-
-impl<'de, 'a> MapAccess<'de> for &'de mut SerbfSeperatedDeserializer<'a, 'de> {
+impl<'de> MapAccess<'de> for SerbfSeperatedDeserializer<'_, 'de> {
     type Error = SerbfError;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
@@ -478,4 +1097,4 @@ impl<'de, 'a> MapAccess<'de> for &'de mut SerbfSeperatedDeserializer<'a, 'de> {
     fn size_hint(&self) -> Option<usize> {
         Some(self.len)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,379 @@
+use std::fmt::Display;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::envelope::Envelope;
+use crate::error::SerbfError;
+use crate::schema::{Container, Field, Format, Schema, VariantFormat};
+use crate::tag::Tag;
+
+/// Writes an annotated hex dump of `data` to `out`: a line per value with its offset, its bytes
+/// and what they decode to, indented by nesting depth.
+///
+/// Self-describing data is decoded from its tags, otherwise as described by `schema`. Without
+/// a schema the bytes are guessed at as strings, varints or single bytes, marked with a `?`.
+/// The lines before a decoding error are still written.
+pub fn dump<W: Write>(data: &[u8], config: SerbfConfig, schema: Option<&Schema>, out: &mut W) -> Result<(), SerbfError> {
+    dump_payload(data, 0, config, schema, out)
+}
+
+/// Same as `dump`, for data that starts with an envelope header, which is dumped as well.
+pub fn dump_with_envelope<W: Write>(data: &[u8], schema: Option<&Schema>, out: &mut W) -> Result<(), SerbfError> {
+    let mut header = Cursor::new(data);
+
+    let envelope = Envelope::read(&mut header)?;
+    let start = header.position() as usize;
+
+    let type_id = match envelope.type_id {
+        Some(v) => { format!(", type id {:#x}", v) }
+        None => { String::new() }
+    };
+
+    let label = format!("envelope v{}{}: {:?}", envelope.version, type_id, envelope.config);
+    write_line(out, 0, &data[..start], 0, label)?;
+
+    dump_payload(data, start, envelope.config, schema, out)
+}
+
+/// Dumps `data[start..]`, up to the checksum trailer, which is verified over all of `data`.
+fn dump_payload<W: Write>(data: &[u8], start: usize, config: SerbfConfig, schema: Option<&Schema>, out: &mut W) -> Result<(), SerbfError> {
+    let end = match data.len().checked_sub(config.checksum.trailer_len()) {
+        Some(v) if v >= start => { v }
+        _ => { return Err(SerbfError::TruncatedRecord) }
+    };
+
+    let mut dumper = Dumper {
+        de: SerbfDeserializer::with_config_unchecked(&data[start..end], config),
+        out: &mut *out,
+        base: start,
+        depth: 0,
+    };
+
+    let res = match (config.self_describing, schema) {
+        (true, _) => { dumper.tagged("") }
+        (false, Some(schema)) => { dumper.format(schema, &schema.root, "") }
+        (false, None) => { dumper.guess() }
+    };
+
+    res.and_then(|_| dumper.rest("trailing bytes"))?;
+
+    if config.checksum.is_none() {
+        return Ok(());
+    }
+
+    let label = match config.checksum.verify(data) {
+        Ok(_) => { format!("checksum {:?} ok", config.checksum) }
+        Err(e) => { format!("checksum {:?}: {}", config.checksum, e) }
+    };
+
+    write_line(out, end, &data[end..], 0, label)
+}
+
+fn write_line<W: Write>(out: &mut W, offset: usize, bytes: &[u8], depth: usize, label: impl Display) -> Result<(), SerbfError> {
+    let mut hex = bytes.iter().take(8).map(|v| format!("{:02x}", v)).collect::<Vec<_>>();
+
+    if bytes.len() > 8 {
+        hex[7] = "..".to_string();
+    }
+
+    let res = writeln!(out, "{:08x}  {:<23}  {:indent$}{}", offset, hex.join(" "), "", label, indent = depth * 2);
+
+    match res {
+        Ok(_) => { Ok(()) }
+        Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+    }
+}
+
+struct Dumper<'a, 'o, W> {
+    de: SerbfDeserializer<'a>,
+    out: &'o mut W,
+
+    /// Offset of the deserializer's data in the whole input
+    base: usize,
+    depth: usize,
+}
+
+impl<W: Write> Dumper<'_, '_, W> {
+    /// Writes the line of everything read since `start`.
+    fn line(&mut self, start: usize, label: impl Display) -> Result<(), SerbfError> {
+        let bytes = &self.de.data()[start..self.de.position()];
+
+        write_line(self.out, self.base + start, bytes, self.depth, label)
+    }
+
+    fn nested<F>(&mut self, f: F) -> Result<(), SerbfError>
+    where
+        F: FnOnce(&mut Self) -> Result<(), SerbfError>
+    {
+        self.de.enter()?;
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        self.de.leave();
+
+        res
+    }
+
+    /// Writes whatever hasn't been read as a single line.
+    fn rest(&mut self, label: &str) -> Result<(), SerbfError> {
+        let start = self.de.position();
+        let len = self.de.data().len() - start;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        self.de.read_slice(len)?;
+        self.line(start, format!("{} ({} bytes)", label, len))
+    }
+
+    fn tagged(&mut self, name: &str) -> Result<(), SerbfError> {
+        let start = self.de.position();
+
+        match self.de.read_tag()? {
+            Tag::Unit => { self.line(start, format!("{}unit", name)) }
+            Tag::Bool => { let v = self.de.read_bool()?; self.line(start, format!("{}bool {}", name, v)) }
+            Tag::I8 => { let v = self.de.read_i8()?; self.line(start, format!("{}i8 {}", name, v)) }
+            Tag::I16 => { let v = self.de.read_i16()?; self.line(start, format!("{}i16 {}", name, v)) }
+            Tag::I32 => { let v = self.de.read_i32()?; self.line(start, format!("{}i32 {}", name, v)) }
+            Tag::I64 => { let v = self.de.read_i64()?; self.line(start, format!("{}i64 {}", name, v)) }
+            Tag::I128 => { let v = self.de.read_i128()?; self.line(start, format!("{}i128 {}", name, v)) }
+            Tag::U8 => { let v = self.de.read_u8()?; self.line(start, format!("{}u8 {}", name, v)) }
+            Tag::U16 => { let v = self.de.read_u16()?; self.line(start, format!("{}u16 {}", name, v)) }
+            Tag::U32 => { let v = self.de.read_u32()?; self.line(start, format!("{}u32 {}", name, v)) }
+            Tag::U64 => { let v = self.de.read_u64()?; self.line(start, format!("{}u64 {}", name, v)) }
+            Tag::U128 => { let v = self.de.read_u128()?; self.line(start, format!("{}u128 {}", name, v)) }
+            Tag::F32 => { let v = self.de.read_f32()?; self.line(start, format!("{}f32 {}", name, v)) }
+            Tag::F64 => { let v = self.de.read_f64()?; self.line(start, format!("{}f64 {}", name, v)) }
+            Tag::Char => { let v = self.de.read_char()?; self.line(start, format!("{}char {:?}", name, v)) }
+            Tag::Str => { let v = self.de.read_str()?; self.line(start, format!("{}str {:?}", name, v)) }
+            Tag::Bytes => {
+                let len = self.de.read_len()?;
+                self.de.read_slice(len)?;
+
+                self.line(start, format!("{}bytes len {}", name, len))
+            }
+            Tag::None => { self.line(start, format!("{}none", name)) }
+            Tag::Some => {
+                self.line(start, format!("{}some", name))?;
+                self.nested(|d| d.tagged(""))
+            }
+            Tag::Seq => {
+                let len = self.de.read_len()?;
+                self.line(start, format!("{}seq len {}", name, len))?;
+
+                self.nested(|d| (0..len).try_for_each(|i| d.tagged(&format!("[{}] ", i))))
+            }
+            Tag::Map => {
+                let len = self.de.read_len()?;
+                self.line(start, format!("{}map len {}", name, len))?;
+
+                self.nested(|d| {
+                    (0..len).try_for_each(|i| {
+                        d.tagged(&format!("[{}] key ", i))?;
+                        d.tagged(&format!("[{}] value ", i))
+                    })
+                })
+            }
+            Tag::Variant => {
+                let index = self.de.read_variant_index()?;
+                self.line(start, format!("{}variant {}", name, index))?;
+
+                self.nested(|d| d.tagged(""))
+            }
+        }
+    }
+
+    fn format(&mut self, schema: &Schema, format: &Format, name: &str) -> Result<(), SerbfError> {
+        let start = self.de.position();
+
+        match format {
+            Format::Unknown => { Err(SerbfError::Schema("Can't decode a value of unknown format".to_string())) }
+            Format::Unit => { self.line(start, format!("{}unit", name)) }
+            Format::Bool => { let v = self.de.read_bool()?; self.line(start, format!("{}bool {}", name, v)) }
+            Format::I8 => { let v = self.de.read_i8()?; self.line(start, format!("{}i8 {}", name, v)) }
+            Format::I16 => { let v = self.de.read_i16()?; self.line(start, format!("{}i16 {}", name, v)) }
+            Format::I32 => { let v = self.de.read_i32()?; self.line(start, format!("{}i32 {}", name, v)) }
+            Format::I64 => { let v = self.de.read_i64()?; self.line(start, format!("{}i64 {}", name, v)) }
+            Format::I128 => { let v = self.de.read_i128()?; self.line(start, format!("{}i128 {}", name, v)) }
+            Format::U8 => { let v = self.de.read_u8()?; self.line(start, format!("{}u8 {}", name, v)) }
+            Format::U16 => { let v = self.de.read_u16()?; self.line(start, format!("{}u16 {}", name, v)) }
+            Format::U32 => { let v = self.de.read_u32()?; self.line(start, format!("{}u32 {}", name, v)) }
+            Format::U64 => { let v = self.de.read_u64()?; self.line(start, format!("{}u64 {}", name, v)) }
+            Format::U128 => { let v = self.de.read_u128()?; self.line(start, format!("{}u128 {}", name, v)) }
+            Format::F32 => { let v = self.de.read_f32()?; self.line(start, format!("{}f32 {}", name, v)) }
+            Format::F64 => { let v = self.de.read_f64()?; self.line(start, format!("{}f64 {}", name, v)) }
+            Format::Char => { let v = self.de.read_char()?; self.line(start, format!("{}char {:?}", name, v)) }
+            Format::Str => { let v = self.de.read_str()?; self.line(start, format!("{}str {:?}", name, v)) }
+            Format::Bytes => {
+                let len = self.de.read_len()?;
+                self.de.read_slice(len)?;
+
+                self.line(start, format!("{}bytes len {}", name, len))
+            }
+            Format::Option(v) => {
+                match self.de.read_option_tag()? {
+                    false => { self.line(start, format!("{}none", name)) }
+                    true => {
+                        self.line(start, format!("{}some", name))?;
+                        self.nested(|d| d.format(schema, v, ""))
+                    }
+                }
+            }
+            Format::Seq(v) => {
+                let len = self.de.read_len()?;
+                self.line(start, format!("{}seq len {}", name, len))?;
+
+                self.nested(|d| (0..len).try_for_each(|i| d.format(schema, v, &format!("[{}] ", i))))
+            }
+            Format::Tuple(v) => { self.tuple(schema, v, name, "tuple") }
+            Format::Map { key, value } => {
+                let len = self.de.read_len()?;
+                self.line(start, format!("{}map len {}", name, len))?;
+
+                self.nested(|d| {
+                    (0..len).try_for_each(|i| {
+                        d.format(schema, key, &format!("[{}] key ", i))?;
+                        d.format(schema, value, &format!("[{}] value ", i))
+                    })
+                })
+            }
+            Format::Named(type_name) => {
+                let container = match schema.container(type_name) {
+                    Some(v) => { v }
+                    None => { return Err(SerbfError::Schema(format!("Missing definition of {}", type_name))) }
+                };
+
+                match container {
+                    Container::UnitStruct => { self.line(start, format!("{}{}", name, type_name)) }
+                    Container::NewtypeStruct(v) => {
+                        self.de.enter()?;
+                        let res = self.format(schema, v, name);
+                        self.de.leave();
+
+                        res
+                    }
+                    Container::TupleStruct(v) => { self.tuple(schema, v, name, type_name) }
+                    Container::Struct(v) => { self.fields(schema, v, &format!("{}{}", name, type_name)) }
+                    Container::Enum(v) => {
+                        let index = self.de.read_variant_index()?;
+
+                        let variant = match v.get(index as usize) {
+                            Some(v) => { v }
+                            None => { return Err(SerbfError::InvalidEnumID) }
+                        };
+
+                        let label = format!("{}{}::{}", name, type_name, variant.name);
+
+                        match &variant.format {
+                            VariantFormat::Unknown => { Err(SerbfError::Schema(format!("Unknown format of variant {}::{}", type_name, variant.name))) }
+                            VariantFormat::Unit => { self.line(start, label) }
+                            VariantFormat::Newtype(v) => {
+                                self.line(start, label)?;
+                                self.nested(|d| d.format(schema, v, ""))
+                            }
+                            VariantFormat::Tuple(v) => {
+                                self.line(start, label)?;
+                                self.nested(|d| d.tuple(schema, v, "", "tuple"))
+                            }
+                            VariantFormat::Struct(v) => {
+                                self.line(start, label)?;
+                                self.nested(|d| d.fields(schema, v, "struct"))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn tuple(&mut self, schema: &Schema, formats: &[Format], name: &str, label: &str) -> Result<(), SerbfError> {
+        let start = self.de.position();
+
+        let len = self.de.read_tuple_len(formats.len())?;
+
+        match self.de.config().tuple_lengths() {
+            true => { self.line(start, format!("{}{} len {}", name, label, len))? }
+            false => { self.line(start, format!("{}{}", name, label))? }
+        }
+
+        if len != formats.len() {
+            return Err(SerbfError::Schema(format!("Expected a tuple of {} elements, found {}", formats.len(), len)));
+        }
+
+        self.nested(|d| formats.iter().enumerate().try_for_each(|(i, v)| d.format(schema, v, &format!("[{}] ", i))))
+    }
+
+    fn fields(&mut self, schema: &Schema, fields: &[Field], label: &str) -> Result<(), SerbfError> {
+        let start = self.de.position();
+
+        let end = self.de.read_struct_end()?;
+
+        match end {
+            Some(v) => { self.line(start, format!("{} len {}", label, v as usize - self.de.position()))? }
+            None => { self.line(start, label)? }
+        }
+
+        self.nested(|d| {
+            for field in fields {
+                if !d.de.has_struct_fields(end) {
+                    break;
+                }
+
+                d.format(schema, &field.format, &format!("{}: ", field.name))?;
+            }
+
+            let start = d.de.position();
+
+            if let Some(v) = end {
+                if (start as u64) < v {
+                    d.de.read_slice(v as usize - start)?;
+                    d.line(start, "unknown fields")?;
+                }
+            }
+
+            d.de.finish_struct(end)
+        })
+    }
+
+    /// Walks data of unknown layout, taking a varint length followed by as many bytes of UTF-8
+    /// without control characters for a string, then a multi-byte varint and then a single byte.
+    fn guess(&mut self) -> Result<(), SerbfError> {
+        while self.de.position() < self.de.data().len() {
+            let start = self.de.position();
+            let mut probe = self.de.clone();
+
+            let varint = probe.read_len().ok();
+
+            let str = match varint {
+                Some(len) if len > 0 => {
+                    probe.read_slice(len).ok()
+                        .and_then(|v| std::str::from_utf8(v).ok())
+                        .filter(|v| !v.chars().any(char::is_control))
+                }
+                _ => { None }
+            };
+
+            if let Some(v) = str {
+                self.de = probe;
+                self.line(start, format!("str? {:?}", v))?;
+
+                continue;
+            }
+
+            match varint {
+                Some(v) if v > 0x7f => {
+                    self.de.read_len()?;
+                    self.line(start, format!("varint? {}", v))?;
+                }
+                _ => {
+                    let v = self.de.read_u8()?;
+                    self.line(start, format!("u8? {}", v))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
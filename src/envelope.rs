@@ -0,0 +1,248 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use varint_rs::VarintWriter;
+use crate::checksum::Checksum;
+use crate::config::{BitPacking, CharEncoding, Compat, Endian, IntEncoding, SerbfConfig, StructEncoding};
+use crate::de;
+use crate::error::SerbfError;
+
+const FLAG_SELF_DESCRIBING: u64 = 1 << 0;
+const FLAG_BIG_ENDIAN: u64 = 1 << 1;
+const FLAG_VARINT: u64 = 1 << 2;
+const FLAG_LENGTH_PREFIXED_STRUCTS: u64 = 1 << 3;
+const FLAG_CRC32C: u64 = 1 << 4;
+const FLAG_XXHASH64: u64 = 1 << 5;
+const FLAG_CANONICAL: u64 = 1 << 6;
+const FLAG_INTERN_STRINGS: u64 = 1 << 7;
+const FLAG_BITS_PER_STRUCT: u64 = 1 << 8;
+const FLAG_BITS_PER_MESSAGE: u64 = 1 << 9;
+const FLAG_COMPAT_BINCODE: u64 = 1 << 10;
+const FLAG_COMPAT_POSTCARD: u64 = 1 << 11;
+const FLAG_CHAR_UTF8: u64 = 1 << 12;
+const FLAG_CHAR_SCALAR: u64 = 1 << 13;
+const FLAG_STRICT: u64 = 1 << 14;
+
+const KNOWN_FLAGS: u64 = FLAG_SELF_DESCRIBING | FLAG_BIG_ENDIAN | FLAG_VARINT | FLAG_LENGTH_PREFIXED_STRUCTS
+    | FLAG_CRC32C | FLAG_XXHASH64 | FLAG_CANONICAL
+    | FLAG_INTERN_STRINGS | FLAG_BITS_PER_STRUCT | FLAG_BITS_PER_MESSAGE
+    | FLAG_COMPAT_BINCODE | FLAG_COMPAT_POSTCARD | FLAG_CHAR_UTF8 | FLAG_CHAR_SCALAR | FLAG_STRICT;
+
+/// Header identifying a persisted serbf payload and the options it was encoded with.
+///
+/// Layout: the magic bytes, the format version, the encoding flags as a varint and an
+/// optional payload type id (a presence byte followed by a varint).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub version: u8,
+    pub config: SerbfConfig,
+    pub type_id: Option<u64>,
+}
+
+impl Envelope {
+    pub const MAGIC: [u8; 4] = *b"SRBF";
+    pub const VERSION: u8 = 1;
+
+    pub fn new(config: SerbfConfig) -> Self {
+        Self {
+            version: Self::VERSION,
+            config,
+            type_id: None,
+        }
+    }
+
+    pub fn with_type_id(mut self, type_id: u64) -> Self {
+        self.type_id = Some(type_id);
+        self
+    }
+
+    pub fn expect_type_id(&self, type_id: u64) -> Result<(), SerbfError> {
+        match self.type_id == Some(type_id) {
+            true => { Ok(()) }
+            false => { Err(SerbfError::TypeIdMismatch { expected: type_id, found: self.type_id }) }
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), SerbfError> {
+        match writer.write_all(&Self::MAGIC) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        match writer.write_u8(self.version) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        match writer.write_u64_varint(flags(&self.config)) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        let res = match self.type_id {
+            None => { writer.write_u8(0) }
+            Some(v) => { writer.write_u8(1).and_then(|_| writer.write_u64_varint(v)) }
+        };
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, SerbfError> {
+        let mut magic = [0; 4];
+
+        match reader.read_exact(&mut magic) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        if magic != Self::MAGIC {
+            return Err(SerbfError::InvalidMagic);
+        }
+
+        let version = match reader.read_u8() {
+            Ok(v) if v == Self::VERSION => { v }
+            Ok(v) => { return Err(SerbfError::UnsupportedVersion(v)) }
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        };
+
+        // The header is always written with minimal varints
+        let config = config(de::read_varint(reader, true)?)?;
+
+        let type_id = match reader.read_u8() {
+            Ok(0) => { None }
+            Ok(1) => { Some(de::read_varint(reader, true)?) }
+            Ok(v) => { return Err(SerbfError::InvalidOptionTag(v)) }
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        };
+
+        Ok(Self {
+            version,
+            config,
+            type_id,
+        })
+    }
+}
+
+pub(crate) fn flags(config: &SerbfConfig) -> u64 {
+    let mut flags = 0;
+
+    if config.self_describing {
+        flags |= FLAG_SELF_DESCRIBING;
+    }
+
+    if config.endian == Endian::Big {
+        flags |= FLAG_BIG_ENDIAN;
+    }
+
+    if config.int_encoding == IntEncoding::Varint {
+        flags |= FLAG_VARINT;
+    }
+
+    if config.struct_encoding == StructEncoding::LengthPrefixed {
+        flags |= FLAG_LENGTH_PREFIXED_STRUCTS;
+    }
+
+    match config.checksum {
+        Checksum::None => {}
+        Checksum::Crc32c => { flags |= FLAG_CRC32C }
+        Checksum::XxHash64 => { flags |= FLAG_XXHASH64 }
+    }
+
+    if config.canonical {
+        flags |= FLAG_CANONICAL;
+    }
+
+    if config.intern_strings {
+        flags |= FLAG_INTERN_STRINGS;
+    }
+
+    match config.bit_packing {
+        BitPacking::Off => {}
+        BitPacking::PerStruct => { flags |= FLAG_BITS_PER_STRUCT }
+        BitPacking::PerMessage => { flags |= FLAG_BITS_PER_MESSAGE }
+    }
+
+    match config.compat {
+        Compat::Off => {}
+        Compat::Bincode => { flags |= FLAG_COMPAT_BINCODE }
+        Compat::Postcard => { flags |= FLAG_COMPAT_POSTCARD }
+    }
+
+    match config.char_encoding {
+        CharEncoding::Str => {}
+        CharEncoding::Utf8 => { flags |= FLAG_CHAR_UTF8 }
+        CharEncoding::Scalar => { flags |= FLAG_CHAR_SCALAR }
+    }
+
+    if config.strict {
+        flags |= FLAG_STRICT;
+    }
+
+    flags
+}
+
+pub(crate) fn config(flags: u64) -> Result<SerbfConfig, SerbfError> {
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(SerbfError::UnknownFlags(flags & !KNOWN_FLAGS));
+    }
+
+    let endian = match flags & FLAG_BIG_ENDIAN != 0 {
+        true => { Endian::Big }
+        false => { Endian::Little }
+    };
+
+    let int_encoding = match flags & FLAG_VARINT != 0 {
+        true => { IntEncoding::Varint }
+        false => { IntEncoding::Fixed }
+    };
+
+    let struct_encoding = match flags & FLAG_LENGTH_PREFIXED_STRUCTS != 0 {
+        true => { StructEncoding::LengthPrefixed }
+        false => { StructEncoding::Sequence }
+    };
+
+    let checksum = match (flags & FLAG_CRC32C != 0, flags & FLAG_XXHASH64 != 0) {
+        (false, false) => { Checksum::None }
+        (true, false) => { Checksum::Crc32c }
+        (false, true) => { Checksum::XxHash64 }
+        (true, true) => { return Err(SerbfError::UnknownFlags(FLAG_CRC32C | FLAG_XXHASH64)) }
+    };
+
+    let bit_packing = match (flags & FLAG_BITS_PER_STRUCT != 0, flags & FLAG_BITS_PER_MESSAGE != 0) {
+        (false, false) => { BitPacking::Off }
+        (true, false) => { BitPacking::PerStruct }
+        (false, true) => { BitPacking::PerMessage }
+        (true, true) => { return Err(SerbfError::UnknownFlags(FLAG_BITS_PER_STRUCT | FLAG_BITS_PER_MESSAGE)) }
+    };
+
+    let compat = match (flags & FLAG_COMPAT_BINCODE != 0, flags & FLAG_COMPAT_POSTCARD != 0) {
+        (false, false) => { Compat::Off }
+        (true, false) => { Compat::Bincode }
+        (false, true) => { Compat::Postcard }
+        (true, true) => { return Err(SerbfError::UnknownFlags(FLAG_COMPAT_BINCODE | FLAG_COMPAT_POSTCARD)) }
+    };
+
+    let char_encoding = match (flags & FLAG_CHAR_UTF8 != 0, flags & FLAG_CHAR_SCALAR != 0) {
+        (false, false) => { CharEncoding::Str }
+        (true, false) => { CharEncoding::Utf8 }
+        (false, true) => { CharEncoding::Scalar }
+        (true, true) => { return Err(SerbfError::UnknownFlags(FLAG_CHAR_UTF8 | FLAG_CHAR_SCALAR)) }
+    };
+
+    Ok(SerbfConfig {
+        self_describing: flags & FLAG_SELF_DESCRIBING != 0,
+        endian,
+        int_encoding,
+        struct_encoding,
+        checksum,
+        canonical: flags & FLAG_CANONICAL != 0,
+        intern_strings: flags & FLAG_INTERN_STRINGS != 0,
+        bit_packing,
+        compat,
+        char_encoding,
+        strict: flags & FLAG_STRICT != 0,
+    })
+}
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use std::io::Error as IOError;
+use std::str::Utf8Error;
 use std::string::FromUtf8Error;
 use std::sync::Arc;
 use thiserror::Error;
@@ -18,11 +19,92 @@ pub enum SerbfError {
     #[error("Invalid char")]
     InvalidChar,
 
+    #[error("Invalid bool: {0}")]
+    InvalidBool(u8),
+
+    #[error("Invalid option tag: {0}")]
+    InvalidOptionTag(u8),
+
     #[error("UTF8 Error: {0}")]
     UTF8Error(#[from] FromUtf8Error),
 
+    #[error("UTF8 Error: {0}")]
+    UTF8StrError(#[from] Utf8Error),
+
     #[error("Unknown size is not supported")]
     UnknownSize,
+
+    #[error("Invalid type tag: {0}")]
+    InvalidTag(u8),
+
+    #[error("Decoding without a type hint requires the self-describing mode")]
+    AnyNotSupported,
+
+    #[error("Schema error: {0}")]
+    Schema(String),
+
+    #[error("Struct fields exceed the struct's byte length")]
+    StructOverrun,
+
+    #[error("Invalid envelope magic bytes")]
+    InvalidMagic,
+
+    #[error("Unsupported format version: {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Unknown encoding flags: {0:#x}")]
+    UnknownFlags(u64),
+
+    #[error("Expected payload type id {expected}, found {found:?}")]
+    TypeIdMismatch { expected: u64, found: Option<u64> },
+
+    #[error("Checksum mismatch: expected {expected:#x}, found {found:#x}")]
+    ChecksumMismatch { expected: u64, found: u64 },
+
+    #[error("Unsupported compression algorithm: {0}")]
+    UnsupportedCompression(u8),
+
+    #[error("Decompression failed: {0}")]
+    Decompression(String),
+
+    #[error("Decompressed payload exceeds {0} bytes")]
+    DecompressionLimit(usize),
+
+    #[error("Encryption failed")]
+    Encryption,
+
+    #[error("Authentication failed, wrong key or tampered data")]
+    AuthenticationFailed,
+
+    #[error("Unsupported cipher: {0}")]
+    UnsupportedCipher(u8),
+
+    #[error("Varint is not minimally encoded")]
+    NonCanonicalVarint,
+
+    #[error("NaN is not canonically encoded")]
+    NonCanonicalNaN,
+
+    #[error("Record is truncated")]
+    TruncatedRecord,
+
+    #[error("Varint overflows its type")]
+    VarintOverflow,
+
+    #[error("Length {0} overflows usize")]
+    LengthOverflow(u64),
+
+    #[error("Key is already in the container")]
+    DuplicateKey,
+
+    #[error("Reference to unknown interned string: {0}")]
+    InvalidStringRef(usize),
+
+    #[error("Recursion limit of {0} nested values exceeded")]
+    RecursionLimit(usize),
+
+    #[error("{0} bytes of trailing data")]
+    TrailingData(usize),
 }
 
 impl serde::ser::Error for SerbfError {
@@ -41,4 +123,4 @@ impl serde::de::Error for SerbfError {
     {
         SerbfError::Message(msg.to_string())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,69 @@
+use std::hash::Hasher;
+use std::io;
+use std::io::Write;
+use serde::Serialize;
+use crate::config::SerbfConfig;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+/// Forwards everything written to it into a `Hasher`.
+///
+/// Digests that implement `Write` themselves (like the RustCrypto hashes) can be passed to
+/// `SerbfSerializer::from_writer` directly.
+#[derive(Clone, Debug, Default)]
+pub struct HashWriter<H> {
+    hasher: H,
+}
+
+impl<H: Hasher> HashWriter<H> {
+    pub fn new(hasher: H) -> Self {
+        Self {
+            hasher,
+        }
+    }
+
+    pub fn into_inner(self) -> H {
+        self.hasher
+    }
+}
+
+impl<H: Hasher> Write for HashWriter<H> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.write(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes the canonical encoding of `value`, so equal values hash equally across runs and
+/// map iteration orders.
+pub fn hash<H, T>(value: &T) -> Result<u64, SerbfError>
+where
+    H: Hasher + Default,
+    T: ?Sized + Serialize
+{
+    let mut hasher = H::default();
+
+    hash_into(value, &mut hasher)?;
+
+    Ok(hasher.finish())
+}
+
+/// Streams the canonical encoding of `value` into `hasher`.
+pub fn hash_into<H, T>(value: &T, hasher: &mut H) -> Result<(), SerbfError>
+where
+    H: Hasher,
+    T: ?Sized + Serialize
+{
+    let mut serializer = SerbfSerializer::from_writer(HashWriter::new(hasher), SerbfConfig::canonical());
+
+    value.serialize(&mut serializer)?;
+
+    serializer.finish()?;
+
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use std::fmt::Formatter;
+use std::marker::PhantomData;
+use serde::de::value::BorrowedBytesDeserializer;
+use serde::de::{DeserializeSeed, Error, IntoDeserializer, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::envelope;
+use crate::error::SerbfError;
+use crate::tag::Tag;
+
+/// Newtype name that makes `SerbfDeserializer` hand the raw sequence to `LazySeq`.
+pub(crate) const TOKEN: &str = "$serbf::private::LazySeq";
+
+/// Sequence borrowed from the input and decoded one element at a time.
+///
+/// Deserializing walks the elements once to find where the sequence ends, without keeping
+/// them. Only `SerbfDeserializer` can produce a `LazySeq`, and only when it neither interns
+/// strings nor packs bits.
+pub struct LazySeq<'de, T> {
+    data: &'de [u8],
+    len: usize,
+    config: SerbfConfig,
+    offsets: Option<Vec<usize>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: Deserialize<'de>> LazySeq<'de, T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The encoded elements, without the length prefix.
+    pub fn as_bytes(&self) -> &'de [u8] {
+        self.data
+    }
+
+    pub fn iter(&self) -> LazySeqIter<'de, T> {
+        LazySeqIter {
+            de: SerbfDeserializer::with_config_unchecked(self.data, self.config),
+            remaining: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Records where every element starts, so `get` doesn't have to decode the ones before it.
+    pub fn build_index(&mut self) -> Result<(), SerbfError> {
+        let mut de = SerbfDeserializer::with_config_unchecked(self.data, self.config);
+        let mut offsets = Vec::with_capacity(self.len);
+
+        for _ in 0..self.len {
+            offsets.push(de.position());
+
+            T::deserialize(&mut de)?;
+        }
+
+        self.offsets = Some(offsets);
+
+        Ok(())
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.offsets.is_some()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Result<T, SerbfError>> {
+        let offset = match &self.offsets {
+            Some(v) => { *v.get(index)? }
+            None => { return self.iter().nth(index) }
+        };
+
+        let mut de = SerbfDeserializer::with_config_unchecked(&self.data[offset..], self.config);
+
+        Some(T::deserialize(&mut de))
+    }
+}
+
+pub struct LazySeqIter<'de, T> {
+    de: SerbfDeserializer<'de>,
+    remaining: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: Deserialize<'de>> Iterator for LazySeqIter<'de, T> {
+    type Item = Result<T, SerbfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match T::deserialize(&mut self.de) {
+            Ok(v) => { self.remaining -= 1; Some(Ok(v)) }
+            Err(e) => { self.remaining = 0; Some(Err(e)) }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+impl<'de, T> Serialize for LazySeq<'de, T>
+where
+    T: Deserialize<'de> + Serialize
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+
+        for v in self.iter() {
+            match v {
+                Ok(v) => { seq.serialize_element(&v)?; }
+                Err(e) => { return Err(serde::ser::Error::custom(e)) }
+            }
+        }
+
+        seq.end()
+    }
+}
+
+impl<'de: 'a, 'a, T: Deserialize<'de>> Deserialize<'de> for LazySeq<'a, T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, LazySeqVisitor(PhantomData))
+    }
+}
+
+struct LazySeqVisitor<'a, T>(PhantomData<fn() -> LazySeq<'a, T>>);
+
+impl<'de: 'a, 'a, T: Deserialize<'de>> Visitor<'de> for LazySeqVisitor<'a, T> {
+    type Value = LazySeq<'a, T>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a sequence decoded by serbf")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let not_serbf = || A::Error::custom("LazySeq can only be deserialized by serbf");
+
+        let len = seq.size_hint().ok_or_else(not_serbf)?;
+
+        for _ in 0..len {
+            seq.next_element_seed(Skip::<T>(PhantomData))?.ok_or_else(not_serbf)?;
+        }
+
+        let data = seq.next_element_seed(Span)?.ok_or_else(not_serbf)?;
+        let flags = seq.next_element::<u64>()?.ok_or_else(not_serbf)?;
+
+        let config = match envelope::config(flags) {
+            Ok(v) => { v }
+            Err(e) => { return Err(A::Error::custom(e)) }
+        };
+
+        Ok(LazySeq {
+            data,
+            len,
+            config,
+            offsets: None,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Decodes an element only to step over it.
+struct Skip<T>(PhantomData<fn() -> T>);
+
+impl<'de, T: Deserialize<'de>> DeserializeSeed<'de> for Skip<T> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        T::deserialize(deserializer).map(|_| ())
+    }
+}
+
+struct Span;
+
+impl<'de> DeserializeSeed<'de> for Span {
+    type Value = &'de [u8];
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        <&'de [u8]>::deserialize(deserializer)
+    }
+}
+
+/// Yields the elements, then the bytes they span, then the config flags to decode them with.
+struct LazySeqAccess<'a, 'de> {
+    de: &'a mut SerbfDeserializer<'de>,
+    remaining: usize,
+    start: usize,
+    stage: u8,
+}
+
+pub(crate) fn visit_lazy_seq<'de, V>(de: &mut SerbfDeserializer<'de>, visitor: V) -> Result<V::Value, SerbfError>
+where
+    V: Visitor<'de>
+{
+    if de.config().interns_strings() {
+        return Err(SerbfError::Message("LazySeq can't decode interned strings".to_string()));
+    }
+
+    if de.config().packs_bits() {
+        return Err(SerbfError::Message("LazySeq can't decode packed bits".to_string()));
+    }
+
+    de.expect_tag(Tag::Seq)?;
+
+    let len = de.read_len()?;
+    let start = de.position();
+
+    visitor.visit_seq(LazySeqAccess { de, remaining: len, start, stage: 0 })
+}
+
+impl<'de> SeqAccess<'de> for LazySeqAccess<'_, 'de> {
+    type Error = SerbfError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>
+    {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+
+            return seed.deserialize(&mut *self.de).map(Some);
+        }
+
+        self.stage = self.stage.saturating_add(1);
+
+        match self.stage {
+            1 => {
+                let data = &self.de.data()[self.start..self.de.position()];
+
+                seed.deserialize(BorrowedBytesDeserializer::new(data)).map(Some)
+            }
+            2 => {
+                let flags = envelope::flags(self.de.config());
+
+                seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(flags)).map(Some)
+            }
+            _ => { Ok(None) }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
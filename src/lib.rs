@@ -1,15 +1,45 @@
-use std::collections::HashMap;
-use serde::ser::SerializeMap;
-use serde::{Deserialize, Deserializer, Serialize};
-use crate::de::SerbfDeserializer;
-use crate::ser::SerbfSerializer;
-
 mod ser;
 mod error;
 mod de;
+mod config;
+mod tag;
+mod value;
+mod envelope;
+mod checksum;
+mod hash;
+mod record;
+mod lazy;
+mod container;
+mod dump;
+mod transcode;
+pub mod schema;
+pub mod compress;
+pub mod columnar;
+pub mod with;
+#[cfg(any(feature = "chacha20poly1305", feature = "aes-gcm"))]
+pub mod seal;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+
+pub use crate::checksum::Checksum;
+pub use crate::config::{BitPacking, CharEncoding, Compat, Endian, IntEncoding, SerbfConfig, StructEncoding};
+pub use crate::container::{ContainerReader, ContainerWriter};
+pub use crate::de::SerbfDeserializer;
+pub use crate::dump::{dump, dump_with_envelope};
+pub use crate::envelope::Envelope;
+pub use crate::error::SerbfError;
+pub use crate::hash::{hash, hash_into, HashWriter};
+pub use crate::lazy::{LazySeq, LazySeqIter};
+pub use crate::record::{RecordReader, RecordWriter};
+pub use crate::ser::SerbfSerializer;
+pub use crate::transcode::{transcode, transcode_from, transcode_with_schema};
+pub use crate::value::Value;
 
 #[test]
 fn ser() {
+    use std::collections::HashMap;
+    use serde::{Deserialize, Serialize};
+
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
     struct TestStruct {
         num_u8: u8,
@@ -74,6 +104,8 @@ fn ser() {
 
 #[test]
 fn de() {
+    use serde::{Deserialize, Serialize};
+
     #[derive(Serialize, Deserialize, PartialEq, Debug)]
     struct TestStruct {
         num_u8: u8,
@@ -127,9 +159,1951 @@ fn de() {
         // ])
     };
 
-    let mut deserializer = SerbfDeserializer::new(&[42, 42, 0, 42, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 214, 214, 255, 214, 255, 255, 255, 214, 255, 255, 255, 255, 255, 255, 255, 214, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, 1, 67, 14, 72, 97, 108, 108, 111, 101, 97, 32, 70, 114, 105, 101, 110, 100, 6, 78, 117, 97, 117, 97, 110, 1, 42, 11, 42, 41, 67, 69, 1, 3, 5, 6, 99, 0, 255, 2, 69, 0, 0, 0, 69, 0, 0, 0, 3, 2, 45, 49, 1, 0, 0, 0, 2, 52, 50, 42, 0, 0, 0, 2, 54, 57, 69, 0, 0, 0]);
+    let mut deserializer = SerbfDeserializer::new(&[42, 42, 0, 42, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, 42, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 214, 214, 255, 214, 255, 255, 255, 214, 255, 255, 255, 255, 255, 255, 255, 214, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, 1, 67, 10, 116, 101, 115, 116, 95, 115, 108, 105, 99, 101, 6, 78, 117, 97, 117, 97, 110, 1, 42, 11, 42, 41, 67, 69, 1, 3, 5, 6, 99, 0, 255, 2, 69, 0, 0, 0, 69, 0, 0, 0, 3, 2, 45, 49, 1, 0, 0, 0, 2, 52, 50, 42, 0, 0, 0, 2, 54, 57, 69, 0, 0, 0]);
 
     let res = TestStruct::deserialize(&mut deserializer).unwrap();
 
     assert_eq!(testy, res);
 }
+
+#[test]
+fn value() {
+    use serde::{Deserialize, Serialize};
+
+    let value = Value::Map(vec![
+        (Value::String("num".to_string()), Value::U32(42)),
+        (Value::String("neg".to_string()), Value::I64(-42)),
+        (Value::String("float".to_string()), Value::F64(0.5)),
+        (Value::String("char".to_string()), Value::Char('C')),
+        (Value::String("bytes".to_string()), Value::Bytes(vec![42, 0, 255])),
+        (Value::String("none".to_string()), Value::Option(None)),
+        (Value::String("some".to_string()), Value::Option(Some(Box::new(Value::Bool(true))))),
+        (Value::String("seq".to_string()), Value::Seq(vec![Value::U8(1), Value::Unit])),
+        (Value::String("variant".to_string()), Value::Variant(3, Box::new(Value::U16(69)))),
+    ]);
+
+    let mut serializer = SerbfSerializer::with_config(SerbfConfig::self_describing());
+
+    value.serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, SerbfConfig::self_describing()).unwrap();
+
+    assert_eq!(value, Value::deserialize(&mut deserializer).unwrap());
+
+    let mut deserializer = SerbfDeserializer::new(&buf);
+
+    assert!(matches!(Value::deserialize(&mut deserializer), Err(SerbfError::AnyNotSupported)));
+}
+
+#[test]
+fn self_describing() {
+    use std::collections::HashMap;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum TestEnum {
+        Unit,
+        Newtype(i32),
+        Tuple(u8, String),
+        Struct { a: u64, b: Option<char> },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct TestStruct {
+        num: u32,
+        str: String,
+        enums: Vec<TestEnum>,
+        map: HashMap<String, u32>,
+    }
+
+    let testy = TestStruct {
+        num: 42,
+        str: "Halloea Friend".to_string(),
+        enums: vec![
+            TestEnum::Unit,
+            TestEnum::Newtype(-42),
+            TestEnum::Tuple(69, "Nuauan".to_string()),
+            TestEnum::Struct { a: 42, b: Some('C') },
+        ],
+        map: HashMap::from([("69".to_string(), 69)]),
+    };
+
+    for config in [SerbfConfig::new(), SerbfConfig::self_describing()] {
+        let mut serializer = SerbfSerializer::with_config(config);
+
+        testy.serialize(&mut serializer).unwrap();
+
+        let buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+        assert_eq!(testy, TestStruct::deserialize(&mut deserializer).unwrap());
+    }
+
+    let mut serializer = SerbfSerializer::with_config(SerbfConfig::self_describing());
+
+    testy.serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, SerbfConfig::self_describing()).unwrap();
+
+    match Value::deserialize(&mut deserializer).unwrap() {
+        Value::Map(v) => { assert_eq!(v[0], (Value::String("num".to_string()), Value::U32(42))) }
+        v => { panic!("expected a map, got {:?}", v) }
+    }
+}
+
+#[test]
+fn schema() {
+    use serde::{Deserialize, Serialize};
+    use crate::schema::{Container, Field, Format, Schema, Variant, VariantFormat};
+
+    #[derive(Serialize, Deserialize)]
+    enum TestEnum {
+        Unit,
+        Newtype(i32),
+        Tuple(u8, String),
+        Struct { a: u64, b: Option<char> },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TestStruct {
+        num: u32,
+        str: &'static str,
+        tuple: (u8, [i16; 2]),
+        enums: Vec<TestEnum>,
+        children: Vec<TestStruct>,
+    }
+
+    let schema = Schema::trace::<TestStruct>().unwrap();
+
+    assert_eq!(schema.root, Format::Named("TestStruct".to_string()));
+
+    assert_eq!(schema.container("TestStruct"), Some(&Container::Struct(vec![
+        Field { name: "num".to_string(), format: Format::U32 },
+        Field { name: "str".to_string(), format: Format::Str },
+        Field { name: "tuple".to_string(), format: Format::Tuple(vec![Format::U8, Format::Tuple(vec![Format::I16, Format::I16])]) },
+        Field { name: "enums".to_string(), format: Format::Seq(Box::new(Format::Named("TestEnum".to_string()))) },
+        Field { name: "children".to_string(), format: Format::Seq(Box::new(Format::Named("TestStruct".to_string()))) },
+    ])));
+
+    assert_eq!(schema.container("TestEnum"), Some(&Container::Enum(vec![
+        Variant { name: "Unit".to_string(), format: VariantFormat::Unit },
+        Variant { name: "Newtype".to_string(), format: VariantFormat::Newtype(Format::I32) },
+        Variant { name: "Tuple".to_string(), format: VariantFormat::Tuple(vec![Format::U8, Format::Str]) },
+        Variant { name: "Struct".to_string(), format: VariantFormat::Struct(vec![
+            Field { name: "a".to_string(), format: Format::U64 },
+            Field { name: "b".to_string(), format: Format::Option(Box::new(Format::Char)) },
+        ]) },
+    ])));
+
+    let mut serializer = SerbfSerializer::new();
+
+    schema.serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::new(&buf);
+
+    assert_eq!(schema, Schema::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn schema_recursive() {
+    use serde::Deserialize;
+    use crate::schema::{Container, Format, Schema, Variant, VariantFormat};
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum List {
+        Cons(i32, Box<List>),
+        Nil,
+    }
+
+    let schema = Schema::trace::<List>().unwrap();
+
+    assert_eq!(schema.container("List"), Some(&Container::Enum(vec![
+        Variant { name: "Cons".to_string(), format: VariantFormat::Tuple(vec![Format::I32, Format::Named("List".to_string())]) },
+        Variant { name: "Nil".to_string(), format: VariantFormat::Unit },
+    ])));
+}
+
+#[test]
+fn schema_decode() {
+    use serde::{Deserialize, Serialize};
+    use crate::schema::Schema;
+
+    #[derive(Serialize, Deserialize)]
+    enum TestEnum {
+        Unit,
+        Struct { a: u64, b: Option<char> },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct TestStruct {
+        num: u32,
+        str: String,
+        tuple: (u8, i16),
+        enums: Vec<TestEnum>,
+    }
+
+    let testy = TestStruct {
+        num: 42,
+        str: "Halloea Friend".to_string(),
+        tuple: (69, -42),
+        enums: vec![TestEnum::Struct { a: 42, b: Some('C') }, TestEnum::Unit],
+    };
+
+    let mut serializer = SerbfSerializer::new();
+
+    testy.serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let schema = Schema::trace::<TestStruct>().unwrap();
+
+    let mut deserializer = SerbfDeserializer::new(&buf);
+
+    assert_eq!(schema.decode(&mut deserializer).unwrap(), Value::Map(vec![
+        (Value::String("num".to_string()), Value::U32(42)),
+        (Value::String("str".to_string()), Value::String("Halloea Friend".to_string())),
+        (Value::String("tuple".to_string()), Value::Seq(vec![Value::U8(69), Value::I16(-42)])),
+        (Value::String("enums".to_string()), Value::Seq(vec![
+            Value::Variant(1, Box::new(Value::Map(vec![
+                (Value::String("a".to_string()), Value::U64(42)),
+                (Value::String("b".to_string()), Value::Option(Some(Box::new(Value::Char('C'))))),
+            ]))),
+            Value::Variant(0, Box::new(Value::Unit)),
+        ])),
+    ]));
+}
+
+#[test]
+fn struct_evolution() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Inner {
+        a: u8,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V1 {
+        num: u32,
+        inner: Inner,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct V2 {
+        num: u32,
+        inner: Inner,
+        #[serde(default)]
+        str: String,
+        #[serde(default)]
+        some: Option<u8>,
+    }
+
+    let config = SerbfConfig {
+        struct_encoding: StructEncoding::LengthPrefixed,
+        ..SerbfConfig::default()
+    };
+
+    let v1 = V1 { num: 42, inner: Inner { a: 69 } };
+    let v2 = V2 { num: 42, inner: Inner { a: 69 }, str: "Nuauan".to_string(), some: Some(1) };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+
+    (&v1, 7u8).serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+    assert_eq!(
+        <(V2, u8)>::deserialize(&mut deserializer).unwrap(),
+        (V2 { num: 42, inner: Inner { a: 69 }, str: String::new(), some: None }, 7),
+    );
+
+    let mut serializer = SerbfSerializer::with_config(config);
+
+    (&v2, 7u8).serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+    assert_eq!(<(V1, u8)>::deserialize(&mut deserializer).unwrap(), (v1, 7));
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+    assert_eq!(<(V2, u8)>::deserialize(&mut deserializer).unwrap(), (v2, 7));
+
+    // A packed bool written last leaves padding bits that newer fields don't read from
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Flags1 {
+        num: u32,
+        inner: Inner,
+        on: bool,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Flags2 {
+        num: u32,
+        inner: Inner,
+        on: bool,
+        #[serde(default = "yes")]
+        enabled: bool,
+        #[serde(default)]
+        some: Option<u8>,
+    }
+
+    fn yes() -> bool {
+        true
+    }
+
+    let flags1 = Flags1 { num: 42, inner: Inner { a: 69 }, on: true };
+    let flags2 = Flags2 { num: 42, inner: Inner { a: 69 }, on: true, enabled: false, some: Some(1) };
+
+    for bit_packing in [BitPacking::PerStruct, BitPacking::PerMessage] {
+        let config = SerbfConfig { bit_packing, ..config };
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        (true, &flags1, false, 7u8).serialize(&mut serializer).unwrap();
+        let buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+        assert_eq!(
+            <(bool, Flags2, bool, u8)>::deserialize(&mut deserializer).unwrap(),
+            (true, Flags2 { num: 42, inner: Inner { a: 69 }, on: true, enabled: true, some: None }, false, 7),
+        );
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        (true, &flags2, false, 7u8).serialize(&mut serializer).unwrap();
+        let buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+        let (before, decoded, after, num) = <(bool, Flags1, bool, u8)>::deserialize(&mut deserializer).unwrap();
+        assert_eq!((before, &decoded, after, num), (true, &flags1, false, 7));
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+        let (before, decoded, after, num) = <(bool, Flags2, bool, u8)>::deserialize(&mut deserializer).unwrap();
+        assert_eq!((before, &decoded, after, num), (true, &flags2, false, 7));
+    }
+}
+
+#[test]
+fn envelope() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct TestStruct {
+        num_u16: u16,
+        num_i64: i64,
+        num_u128: u128,
+        float: f64,
+        str: String,
+    }
+
+    let testy = TestStruct {
+        num_u16: 42,
+        num_i64: -42,
+        num_u128: u128::MAX,
+        float: 0.5,
+        str: "Halloea Friend".to_string(),
+    };
+
+    let config = SerbfConfig {
+        endian: Endian::Big,
+        int_encoding: IntEncoding::Varint,
+        ..SerbfConfig::default()
+    };
+
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config).with_type_id(69)).unwrap();
+
+    testy.serialize(&mut serializer).unwrap();
+
+    let buf = serializer.into_inner();
+
+    assert_eq!(&buf[..9], &[b'S', b'R', b'B', b'F', 1, 0b110, 1, 69, 42]);
+
+    let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&buf).unwrap();
+
+    assert_eq!(envelope.config, config);
+    assert!(envelope.expect_type_id(69).is_ok());
+    assert!(matches!(envelope.expect_type_id(42), Err(SerbfError::TypeIdMismatch { .. })));
+
+    assert_eq!(testy, TestStruct::deserialize(&mut deserializer).unwrap());
+
+    assert!(matches!(SerbfDeserializer::with_envelope(&buf[1..]), Err(SerbfError::InvalidMagic)));
+
+    let mut buf = buf;
+    buf[4] = 2;
+
+    assert!(matches!(SerbfDeserializer::with_envelope(&buf), Err(SerbfError::UnsupportedVersion(2))));
+
+    buf[4] = 1;
+    buf[6] = 2;
+
+    assert!(matches!(SerbfDeserializer::with_envelope(&buf), Err(SerbfError::InvalidOptionTag(2))));
+
+    // Varints in the header are bounded and minimal
+    let mut header = b"SRBF\x01".to_vec();
+    header.extend([0xff; 12]);
+
+    assert!(matches!(SerbfDeserializer::with_envelope(&header), Err(SerbfError::VarintOverflow)));
+    assert!(matches!(SerbfDeserializer::with_envelope(b"SRBF\x01\x86\x00\x00"), Err(SerbfError::NonCanonicalVarint)));
+}
+
+#[test]
+fn checksum() {
+    use serde::{Deserialize, Serialize};
+
+    let testy = (42u32, "Halloea Friend".to_string(), vec![-42i64; 4]);
+
+    for checksum in [Checksum::Crc32c, Checksum::XxHash64] {
+        let config = SerbfConfig {
+            checksum,
+            ..SerbfConfig::default()
+        };
+
+        let mut serializer = SerbfSerializer::with_config(config);
+
+        testy.serialize(&mut serializer).unwrap();
+
+        let mut buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+
+        assert_eq!(testy, <(u32, String, Vec<i64>)>::deserialize(&mut deserializer).unwrap());
+
+        buf[6] ^= 0x10;
+
+        assert!(matches!(SerbfDeserializer::with_config(&buf, config), Err(SerbfError::ChecksumMismatch { .. })));
+
+        let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config)).unwrap();
+
+        testy.serialize(&mut serializer).unwrap();
+
+        let mut buf = serializer.into_inner();
+
+        let (mut deserializer, _) = SerbfDeserializer::with_envelope(&buf).unwrap();
+
+        assert_eq!(testy, <(u32, String, Vec<i64>)>::deserialize(&mut deserializer).unwrap());
+
+        let last = buf.len() - 1;
+        buf[last] ^= 0x01;
+
+        assert!(matches!(SerbfDeserializer::with_envelope(&buf), Err(SerbfError::ChecksumMismatch { .. })));
+    }
+}
+
+#[test]
+fn compression() {
+    use serde::{Deserialize, Serialize};
+    use crate::compress::{Algorithm, CompressedSerializer, Compression, Decompressed};
+
+    let algorithms = [
+        Algorithm::None,
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4,
+        #[cfg(feature = "deflate")]
+        Algorithm::Deflate,
+    ];
+
+    let logs = vec!["GET /index.html 200 Halloea Friend"; 64];
+
+    for algorithm in algorithms {
+        let mut compressed = CompressedSerializer::new(SerbfConfig::default(), Compression::new(algorithm));
+
+        logs.serialize(compressed.serializer()).unwrap();
+
+        let buf = compressed.into_inner().unwrap();
+
+        if algorithm != Algorithm::None {
+            assert!(buf.len() < 34 * 64 / 5);
+        }
+
+        let decompressed = Decompressed::from_slice(&buf).unwrap();
+        let mut deserializer = decompressed.deserializer(SerbfConfig::default()).unwrap();
+
+        assert_eq!(logs, Vec::<&str>::deserialize(&mut deserializer).unwrap());
+
+        let decompressed = Decompressed::from_reader(buf.as_slice()).unwrap();
+        let mut deserializer = decompressed.deserializer(SerbfConfig::default()).unwrap();
+
+        assert_eq!(logs, Vec::<String>::deserialize(&mut deserializer).unwrap());
+
+        // Payloads decompressing past the limit are rejected before they're fully inflated
+        let len = decompressed.as_slice().len();
+
+        assert_eq!(len, Decompressed::from_slice_with_limit(&buf, len).unwrap().as_slice().len());
+        assert_eq!(len, Decompressed::from_reader_with_limit(buf.as_slice(), len).unwrap().as_slice().len());
+        assert!(matches!(Decompressed::from_slice_with_limit(&buf, len - 1), Err(SerbfError::DecompressionLimit(v)) if v == len - 1));
+        assert!(matches!(Decompressed::from_reader_with_limit(buf.as_slice(), 64), Err(SerbfError::DecompressionLimit(64))));
+
+        // Corrupt payloads fail the same way for every codec
+        if algorithm != Algorithm::None {
+            let mut corrupt = buf.clone();
+            let middle = corrupt.len() / 2;
+            corrupt[middle..].fill(0xff);
+
+            assert!(matches!(Decompressed::from_slice(&corrupt), Err(SerbfError::Decompression(_))));
+            assert!(matches!(Decompressed::from_reader(corrupt.as_slice()), Err(SerbfError::Decompression(_))));
+        }
+
+        // Tiny messages skip compression
+        let mut compressed = CompressedSerializer::new(SerbfConfig::default(), Compression::new(algorithm));
+
+        42u32.serialize(compressed.serializer()).unwrap();
+
+        let mut buf = Vec::new();
+        compressed.write_to(&mut buf).unwrap();
+
+        assert_eq!(buf, [0, 42, 0, 0, 0]);
+    }
+}
+
+#[test]
+fn canonical() {
+    use std::collections::{BTreeMap, HashMap};
+    use serde::{Deserialize, Serialize};
+
+    let config = SerbfConfig {
+        int_encoding: IntEncoding::Varint,
+        ..SerbfConfig::canonical()
+    };
+
+    let encode = |value: &HashMap<String, BTreeMap<u32, f64>>| {
+        let mut serializer = SerbfSerializer::with_config(config);
+        value.serialize(&mut serializer).unwrap();
+        serializer.into_inner()
+    };
+
+    let mut forward = HashMap::new();
+    let mut backward = HashMap::new();
+
+    for i in 0..64u32 {
+        forward.insert(format!("key {i}"), BTreeMap::from([(i, i as f64), (300 + i, -1.0)]));
+    }
+
+    for i in (0..64u32).rev() {
+        backward.insert(format!("key {i}"), BTreeMap::from([(i, i as f64), (300 + i, -1.0)]));
+    }
+
+    let buf = encode(&forward);
+
+    assert_eq!(buf, encode(&backward));
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+    assert_eq!(forward, HashMap::deserialize(&mut deserializer).unwrap());
+
+    // Entries are ordered by encoded key bytes, not by value
+    let mut serializer = SerbfSerializer::with_config(config);
+    BTreeMap::from([(1u32, ()), (128, ()), (2, ())]).serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [3, 1, 2, 128, 1]);
+
+    // NaN payloads are normalized
+    let mut serializer = SerbfSerializer::with_config(config);
+    (f32::from_bits(0xffc0_0001), f64::NAN).serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [2, 0x00, 0x00, 0xc0, 0x7f, 0, 0, 0, 0, 0, 0, 0xf8, 0x7f]);
+
+    let mut deserializer = SerbfDeserializer::with_config(&[0x01, 0x00, 0xc0, 0x7f], config).unwrap();
+    assert!(matches!(f32::deserialize(&mut deserializer), Err(SerbfError::NonCanonicalNaN)));
+
+    // Overlong varints only decode outside canonical mode
+    let overlong = [0x80, 0x00];
+
+    let mut deserializer = SerbfDeserializer::with_config(&overlong, config).unwrap();
+    assert!(matches!(u32::deserialize(&mut deserializer), Err(SerbfError::NonCanonicalVarint)));
+
+    let mut deserializer = SerbfDeserializer::with_config(&overlong, SerbfConfig { canonical: false, ..config }).unwrap();
+    assert_eq!(0, u32::deserialize(&mut deserializer).unwrap());
+
+    let mut deserializer = SerbfDeserializer::with_config(&[0x00], config).unwrap();
+    assert_eq!(0, u32::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn content_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+    use serde::Serialize;
+
+    #[derive(Serialize)]
+    struct Response {
+        status: u16,
+        headers: HashMap<String, String>,
+    }
+
+    let headers = (0..32).map(|i| (format!("x-header-{i}"), "Halloea Friend".to_string())).collect::<HashMap<_, _>>();
+    let reversed = (0..32).rev().map(|i| (format!("x-header-{i}"), "Halloea Friend".to_string())).collect::<HashMap<_, _>>();
+
+    let a = crate::hash::<DefaultHasher, _>(&Response { status: 200, headers }).unwrap();
+    let b = crate::hash::<DefaultHasher, _>(&Response { status: 200, headers: reversed.clone() }).unwrap();
+    let c = crate::hash::<DefaultHasher, _>(&Response { status: 404, headers: reversed.clone() }).unwrap();
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    // Streaming hashes exactly the bytes of the buffered encoding
+    let mut serializer = SerbfSerializer::with_config(SerbfConfig::canonical());
+    Response { status: 200, headers: reversed.clone() }.serialize(&mut serializer).unwrap();
+
+    let mut hasher = DefaultHasher::new();
+    std::hash::Hasher::write(&mut hasher, &serializer.into_inner());
+
+    assert_eq!(a, std::hash::Hasher::finish(&hasher));
+
+    // Streamed output matches buffered output, checksum trailer included
+    let config = SerbfConfig {
+        struct_encoding: StructEncoding::LengthPrefixed,
+        checksum: Checksum::Crc32c,
+        ..SerbfConfig::canonical()
+    };
+
+    let value = vec![Response { status: 200, headers: reversed }];
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    value.serialize(&mut serializer).unwrap();
+    let buffered = serializer.into_inner();
+
+    let mut streamed = Vec::new();
+    let mut serializer = SerbfSerializer::from_writer(&mut streamed, config);
+    value.serialize(&mut serializer).unwrap();
+    serializer.finish().unwrap();
+
+    assert_eq!(buffered, streamed);
+    assert!(SerbfDeserializer::with_config(&streamed, config).is_ok());
+}
+
+#[test]
+fn records() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Entry {
+        id: u32,
+        message: String,
+    }
+
+    let config = SerbfConfig {
+        checksum: Checksum::Crc32c,
+        ..SerbfConfig::default()
+    };
+
+    let mut writer = RecordWriter::new(Vec::new(), config);
+
+    for id in 0..5 {
+        writer.write(&Entry { id, message: "Halloea Friend".repeat(id as usize) }).unwrap();
+    }
+
+    let buf = writer.into_inner();
+
+    let entries = RecordReader::<_, Entry>::new(buf.as_slice(), config).collect::<Result<Vec<_>, _>>().unwrap();
+
+    assert_eq!(5, entries.len());
+    assert_eq!(3, entries[3].id);
+
+    // Skipping doesn't decode
+    let mut reader = RecordReader::<_, Entry>::new(buf.as_slice(), config);
+
+    assert_eq!(2, reader.skip_records(2).unwrap());
+    assert_eq!(2, reader.next().unwrap().unwrap().id);
+    assert_eq!(4, reader.nth(1).unwrap().unwrap().id);
+    assert!(reader.next().is_none());
+
+    // A truncated last record is reported once
+    let results = RecordReader::<_, Entry>::new(&buf[..buf.len() - 3], config).collect::<Vec<_>>();
+
+    assert_eq!(5, results.len());
+    assert!(results[..4].iter().all(|v| v.is_ok()));
+    assert!(matches!(results[4], Err(SerbfError::TruncatedRecord)));
+
+    // A corrupt record doesn't break the framing
+    let mut corrupt = buf.clone();
+    corrupt[3] ^= 0x01;
+
+    let results = RecordReader::<_, Entry>::new(corrupt.as_slice(), config).collect::<Vec<_>>();
+
+    assert_eq!(5, results.len());
+    assert!(matches!(results[0], Err(SerbfError::ChecksumMismatch { .. })));
+    assert!(results[1..].iter().all(|v| v.is_ok()));
+
+    // Bytes after the value inside a record are rejected
+    let mut serializer = SerbfSerializer::new();
+    Entry { id: 7, message: "Halloea".to_string() }.serialize(&mut serializer).unwrap();
+    let mut record = serializer.into_inner();
+    record.push(0xaa);
+
+    let mut framed = vec![record.len() as u8];
+    framed.extend(&record);
+
+    let mut writer = RecordWriter::new(framed, SerbfConfig::default());
+    writer.write(&Entry { id: 8, message: String::new() }).unwrap();
+    let framed = writer.into_inner();
+
+    let results = RecordReader::<_, Entry>::new(framed.as_slice(), SerbfConfig::default()).collect::<Vec<_>>();
+
+    assert_eq!(2, results.len());
+    assert!(matches!(results[0], Err(SerbfError::TrailingData(1))));
+    assert_eq!(8, results[1].as_ref().unwrap().id);
+}
+
+#[test]
+fn lazy_seq() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        id: u32,
+        name: String,
+    }
+
+    #[derive(Serialize)]
+    struct Payload {
+        records: Vec<Record>,
+        trailer: u32,
+    }
+
+    #[derive(Deserialize)]
+    struct LazyPayload<'a> {
+        #[serde(borrow)]
+        records: LazySeq<'a, Record>,
+        trailer: u32,
+    }
+
+    let configs = [
+        SerbfConfig::default(),
+        SerbfConfig::self_describing(),
+        SerbfConfig {
+            int_encoding: IntEncoding::Varint,
+            struct_encoding: StructEncoding::LengthPrefixed,
+            ..SerbfConfig::default()
+        },
+    ];
+
+    let records = || (0..100).map(|id| Record { id, name: "Halloea Friend".repeat(id as usize % 3) }).collect::<Vec<_>>();
+
+    for config in configs {
+        let mut serializer = SerbfSerializer::with_config(config);
+        Payload { records: records(), trailer: 0xdead }.serialize(&mut serializer).unwrap();
+        let buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+        let mut payload = LazyPayload::deserialize(&mut deserializer).unwrap();
+
+        assert_eq!(0xdead, payload.trailer);
+        assert_eq!(100, payload.records.len());
+        assert_eq!(100, payload.records.iter().filter(|v| v.is_ok()).count());
+        assert_eq!(42, payload.records.get(42).unwrap().unwrap().id);
+        assert!(payload.records.get(100).is_none());
+
+        payload.records.build_index().unwrap();
+
+        assert!(payload.records.is_indexed());
+        assert_eq!(Record { id: 99, name: "".to_string() }, payload.records.get(99).unwrap().unwrap());
+
+        // Serializing writes the same sequence back
+        let mut serializer = SerbfSerializer::with_config(config);
+        payload.records.serialize(&mut serializer).unwrap();
+
+        let mut expected = SerbfSerializer::with_config(config);
+        records().serialize(&mut expected).unwrap();
+
+        assert_eq!(expected.into_inner(), serializer.into_inner());
+    }
+
+    // Elements decoded on their own couldn't resolve references to strings interned before them,
+    // nor find their bits in bytes packed before them
+    let configs = [
+        SerbfConfig { intern_strings: true, ..SerbfConfig::default() },
+        SerbfConfig { bit_packing: BitPacking::PerStruct, ..SerbfConfig::default() },
+        SerbfConfig { bit_packing: BitPacking::PerMessage, ..SerbfConfig::default() },
+    ];
+
+    for config in configs {
+        let mut serializer = SerbfSerializer::with_config(config);
+        Payload { records: records(), trailer: 0xdead }.serialize(&mut serializer).unwrap();
+        let buf = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+        assert!(matches!(LazyPayload::deserialize(&mut deserializer), Err(SerbfError::Message(_))));
+    }
+
+    let config = SerbfConfig { bit_packing: BitPacking::PerStruct, ..SerbfConfig::default() };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    vec![false, true, false, true].serialize(&mut serializer).unwrap();
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&buf, config).unwrap();
+    assert!(matches!(LazySeq::<bool>::deserialize(&mut deserializer), Err(SerbfError::Message(_))));
+}
+
+#[test]
+fn container() {
+    use std::io::Cursor;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Row {
+        id: u64,
+        name: String,
+    }
+
+    let config = SerbfConfig {
+        checksum: Checksum::Crc32c,
+        ..SerbfConfig::default()
+    };
+
+    let mut writer = ContainerWriter::new(Vec::new(), config).unwrap();
+
+    for id in 0..1000u64 {
+        let row = Row { id, name: format!("Halloea Friend {id}") };
+
+        match id % 2 == 0 {
+            true => { assert_eq!(id as usize, writer.insert(&format!("row-{id}"), &row).unwrap()); }
+            false => { assert_eq!(id as usize, writer.append(&row).unwrap()); }
+        }
+    }
+
+    assert!(matches!(writer.insert("row-0", &Row { id: 0, name: "".to_string() }), Err(SerbfError::DuplicateKey)));
+
+    let buf = writer.finish().unwrap();
+
+    let mut reader = ContainerReader::open(Cursor::new(buf.clone())).unwrap();
+
+    assert_eq!(1000, reader.len());
+    assert_eq!(config, reader.envelope().config);
+    assert_eq!(Some(Row { id: 501, name: "Halloea Friend 501".to_string() }), reader.get(501).unwrap());
+    assert_eq!(Some(Row { id: 42, name: "Halloea Friend 42".to_string() }), reader.get_by_key("row-42").unwrap());
+    assert_eq!(None, reader.get_by_key::<_, Row>("row-43").unwrap());
+    assert_eq!(None, reader.get::<Row>(1000).unwrap());
+
+    // Records are checked one by one
+    let mut corrupt = buf.clone();
+    let offset = corrupt.len() / 2;
+    corrupt[offset] ^= 0xff;
+
+    let mut reader = ContainerReader::open(Cursor::new(corrupt)).unwrap();
+    assert!(reader.get::<Row>(0).is_ok());
+    assert!((0..1000).any(|i| reader.get::<Row>(i).is_err()));
+
+    let truncated = buf[..buf.len() - 1].to_vec();
+    assert!(matches!(ContainerReader::open(Cursor::new(truncated)), Err(SerbfError::InvalidMagic)));
+}
+
+#[test]
+fn columnar() {
+    use serde::{Deserialize, Serialize};
+    use crate::columnar::Columnar;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+    struct Row {
+        timestamp: u64,
+        offset: i32,
+        city: String,
+        score: f32,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Table {
+        name: String,
+        #[serde(with = "crate::columnar")]
+        rows: Vec<Row>,
+    }
+
+    let cities = ["Berlin", "Lisbon", "Osaka"];
+
+    let rows = (0..1000).map(|i| Row {
+        timestamp: 1_700_000_000 + i * 15,
+        offset: (i as i32 % 7) - 3,
+        city: cities[i as usize % 3].to_string(),
+        score: i as f32 / 4.0,
+        tags: vec!["Halloea Friend".to_string(); i as usize % 2],
+    }).collect::<Vec<_>>();
+
+    let table = Table { name: "visits".to_string(), rows: rows.clone() };
+
+    // Raw columns follow the config of the message, except for its checksum
+    let big_endian = SerbfConfig {
+        endian: Endian::Big,
+        intern_strings: true,
+        bit_packing: BitPacking::PerMessage,
+        checksum: Checksum::Crc32c,
+        ..SerbfConfig::default()
+    };
+
+    for config in [SerbfConfig::default(), SerbfConfig::self_describing(), big_endian] {
+        let mut serializer = SerbfSerializer::with_config(config);
+        table.serialize(&mut serializer).unwrap();
+        let columnar = serializer.into_inner();
+
+        let score = match config.endian {
+            Endian::Little => { 0.25f32.to_le_bytes() }
+            Endian::Big => { 0.25f32.to_be_bytes() }
+        };
+
+        assert!(columnar.windows(4).any(|v| v == score));
+
+        let mut deserializer = SerbfDeserializer::with_config(&columnar, config).unwrap();
+        assert_eq!(table, Table::deserialize(&mut deserializer).unwrap());
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        rows.serialize(&mut serializer).unwrap();
+
+        assert!(columnar.len() < serializer.into_inner().len() / 2);
+    }
+
+    // Rows have to be structs
+    let mut serializer = SerbfSerializer::new();
+    assert!(Columnar(vec![1u32, 2]).serialize(&mut serializer).is_err());
+
+    // Corrupt integer columns fail instead of wrapping around
+    #[derive(Serialize)]
+    struct Encoded {
+        rows: u64,
+        columns: Vec<EncodedColumn>,
+    }
+
+    #[derive(Serialize)]
+    enum EncodedColumn {
+        Ints { kind: u8, delta: bool, data: Vec<u8> },
+        Strs { dictionary: Vec<String>, indices: Vec<u8> },
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Small {
+        v: u8,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Named {
+        name: String,
+    }
+
+    let encode = |rows: u64, column: EncodedColumn| {
+        let mut serializer = SerbfSerializer::new();
+        Encoded { rows, columns: vec![column] }.serialize(&mut serializer).unwrap();
+        serializer.into_inner()
+    };
+
+    let ints = |kind: u8, delta: bool, values: &[i128]| {
+        use varint_rs::VarintWriter;
+
+        let mut data = Vec::new();
+
+        for v in values {
+            data.write_i128_varint(*v).unwrap();
+        }
+
+        encode(values.len() as u64, EncodedColumn::Ints { kind, delta, data })
+    };
+
+    let buf = ints(4, false, &[300]);
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!("Column value out of range: 300", Columnar::<Small>::deserialize(&mut deserializer).unwrap_err().to_string());
+
+    let buf = ints(4, true, &[200, i128::MAX]);
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!("Delta overflows i128", Columnar::<Small>::deserialize(&mut deserializer).unwrap_err().to_string());
+
+    let buf = ints(4, true, &[200, 55]);
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!(255, Columnar::<Small>::deserialize(&mut deserializer).unwrap()[1].v);
+
+    // And so do over-long varints
+    let buf = encode(1, EncodedColumn::Ints { kind: 4, delta: false, data: vec![0xff; 20] });
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!(SerbfError::VarintOverflow.to_string(), Columnar::<Small>::deserialize(&mut deserializer).unwrap_err().to_string());
+
+    let buf = encode(1, EncodedColumn::Strs { dictionary: vec!["Halloea".to_string()], indices: vec![0xff; 12] });
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!(SerbfError::VarintOverflow.to_string(), Columnar::<Named>::deserialize(&mut deserializer).unwrap_err().to_string());
+
+    // Row counts have to fit the columns
+    let buf = encode(u64::MAX, EncodedColumn::Ints { kind: 4, delta: false, data: vec![2] });
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert_eq!(format!("{} rows don't fit the column data", u64::MAX), Columnar::<Small>::deserialize(&mut deserializer).unwrap_err().to_string());
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Empty {}
+
+    let mut serializer = SerbfSerializer::new();
+    Encoded { rows: u64::MAX, columns: vec![] }.serialize(&mut serializer).unwrap();
+    let buf = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::new(&buf);
+    assert!(Columnar::<Empty>::deserialize(&mut deserializer).is_err());
+
+    let mut serializer = SerbfSerializer::new();
+    assert!(Columnar(vec![Empty {}]).serialize(&mut serializer).is_err());
+}
+
+#[test]
+fn interning() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event<'a> {
+        region: &'a str,
+        kind: String,
+        initial: char,
+    }
+
+    let regions = ["eu-central-1", "us-east-1"];
+
+    let events = (0..100).map(|i| Event { region: regions[i % 2], kind: "Halloea Friend".to_string(), initial: 'H' }).collect::<Vec<_>>();
+
+    for base in [SerbfConfig::default(), SerbfConfig::self_describing()] {
+        let config = SerbfConfig { intern_strings: true, ..base };
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        events.serialize(&mut serializer).unwrap();
+        let interned = serializer.into_inner();
+
+        let mut serializer = SerbfSerializer::with_config(base);
+        events.serialize(&mut serializer).unwrap();
+
+        assert!(interned.len() * 2 < serializer.into_inner().len());
+
+        let mut deserializer = SerbfDeserializer::with_config(&interned, config).unwrap();
+        let decoded = Vec::<Event>::deserialize(&mut deserializer).unwrap();
+
+        assert_eq!(events, decoded);
+
+        // References resolve to the first occurrence in the input
+        assert_eq!(decoded[0].region.as_ptr(), decoded[98].region.as_ptr());
+        assert!(interned.as_ptr_range().contains(&decoded[99].region.as_ptr()));
+    }
+
+    // Literal "ab", then references to it and to an unknown string
+    let config = SerbfConfig { intern_strings: true, ..SerbfConfig::default() };
+
+    let mut deserializer = SerbfDeserializer::with_config(&[3, 4, b'a', b'b', 1, 3], config).unwrap();
+    assert!(matches!(<(&str, &str, &str)>::deserialize(&mut deserializer), Err(SerbfError::InvalidStringRef(1))));
+
+    // Canonical mode doesn't intern
+    let config = SerbfConfig { intern_strings: true, ..SerbfConfig::canonical() };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    ("ab", "ab").serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [2, 2, b'a', b'b', 2, b'a', b'b']);
+}
+
+#[test]
+fn bit_packing() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Kind {
+        A, B, C, D, E, F, G,
+        H(u8),
+        I { on: bool },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Inner {
+        on: bool,
+        kind: Kind,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Flags {
+        bits: [bool; 20],
+        missing: Option<u8>,
+        present: Option<u8>,
+        kinds: Vec<Kind>,
+        inner: Inner,
+        last: bool,
+    }
+
+    let flags = Flags {
+        bits: std::array::from_fn(|i| i % 3 == 0),
+        missing: None,
+        present: Some(42),
+        kinds: vec![Kind::A, Kind::G, Kind::H(9), Kind::I { on: true }],
+        inner: Inner { on: true, kind: Kind::C },
+        last: true,
+    };
+
+    for struct_encoding in [StructEncoding::Sequence, StructEncoding::LengthPrefixed] {
+        let base = SerbfConfig { struct_encoding, ..SerbfConfig::default() };
+
+        let mut serializer = SerbfSerializer::with_config(base);
+        flags.serialize(&mut serializer).unwrap();
+        let unpacked = serializer.into_inner();
+
+        for bit_packing in [BitPacking::PerStruct, BitPacking::PerMessage] {
+            let config = SerbfConfig { bit_packing, ..base };
+
+            let mut serializer = SerbfSerializer::with_config(config);
+            flags.serialize(&mut serializer).unwrap();
+            let packed = serializer.into_inner();
+
+            assert!(packed.len() * 2 < unpacked.len());
+
+            let mut deserializer = SerbfDeserializer::with_config(&packed, config).unwrap();
+            assert_eq!(flags, Flags::deserialize(&mut deserializer).unwrap());
+
+            let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config)).unwrap();
+            flags.serialize(&mut serializer).unwrap();
+            let enveloped = serializer.into_inner();
+
+            let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&enveloped).unwrap();
+            assert_eq!(envelope.config.bit_packing, bit_packing);
+            assert_eq!(flags, Flags::deserialize(&mut deserializer).unwrap());
+        }
+    }
+
+    // Tuple length, then bits LSB first. Variant 7 escapes to a varint of the index minus 7
+    let config = SerbfConfig { bit_packing: BitPacking::PerMessage, ..SerbfConfig::default() };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    (true, Some(3u8), false, Kind::H(9)).serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [4, 0b0011_1011, 3, 0, 9]);
+
+    // With a byte per struct, unknown trailing fields can still be skipped
+    #[derive(Serialize)]
+    struct New {
+        a: bool,
+        b: bool,
+        c: bool,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Old {
+        a: bool,
+        b: bool,
+    }
+
+    let config = SerbfConfig {
+        struct_encoding: StructEncoding::LengthPrefixed,
+        bit_packing: BitPacking::PerStruct,
+        ..SerbfConfig::default()
+    };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    (true, New { a: false, b: true, c: true }, true).serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&encoded, config).unwrap();
+    assert_eq!(<(bool, Old, bool)>::deserialize(&mut deserializer).unwrap(), (true, Old { a: false, b: true }, true));
+
+    // Self-describing output is never packed
+    let config = SerbfConfig { bit_packing: BitPacking::PerMessage, ..SerbfConfig::self_describing() };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    flags.serialize(&mut serializer).unwrap();
+
+    let mut unpacked = SerbfSerializer::with_config(SerbfConfig::self_describing());
+    flags.serialize(&mut unpacked).unwrap();
+
+    assert_eq!(serializer.into_inner(), unpacked.into_inner());
+}
+
+#[test]
+fn delta() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Series {
+        #[serde(with = "crate::with::delta")]
+        ids: Vec<u64>,
+
+        #[serde(with = "crate::with::delta_of_delta")]
+        timestamps: Vec<i64>,
+    }
+
+    #[derive(Serialize)]
+    struct Plain {
+        ids: Vec<u64>,
+        timestamps: Vec<i64>,
+    }
+
+    let ids = (0..1000).map(|i| 1_000_000 + i * 3).collect::<Vec<u64>>();
+    let timestamps = (0..1000).map(|i| 1_700_000_000_000 + i * 1000 + i % 3).collect::<Vec<i64>>();
+
+    let series = Series { ids: ids.clone(), timestamps: timestamps.clone() };
+
+    for config in [SerbfConfig::default(), SerbfConfig::self_describing()] {
+        let mut serializer = SerbfSerializer::with_config(config);
+        series.serialize(&mut serializer).unwrap();
+        let encoded = serializer.into_inner();
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        Plain { ids: ids.clone(), timestamps: timestamps.clone() }.serialize(&mut serializer).unwrap();
+
+        assert!(encoded.len() * 5 < serializer.into_inner().len());
+
+        let mut deserializer = SerbfDeserializer::with_config(&encoded, config).unwrap();
+        assert_eq!(series, Series::deserialize(&mut deserializer).unwrap());
+    }
+
+    // Extremes round trip exactly
+    let series = Series {
+        ids: vec![u64::MAX, 0, u64::MAX, 1],
+        timestamps: vec![i64::MIN, i64::MAX, i64::MIN, 0, -1],
+    };
+
+    let mut serializer = SerbfSerializer::new();
+    series.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::new(&encoded);
+    assert_eq!(series, Series::deserialize(&mut deserializer).unwrap());
+
+    // First value, first delta, then the differences of the deltas
+    let mut serializer = SerbfSerializer::new();
+    Series { ids: vec![], timestamps: vec![100, 110, 120, 131] }.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), [0, 5, 200, 1, 20, 0, 2]);
+
+    // Values that don't fit the target type are rejected
+    #[derive(Deserialize, Debug)]
+    struct Small {
+        #[serde(with = "crate::with::delta")]
+        _values: Vec<u8>,
+    }
+
+    let mut serializer = SerbfSerializer::new();
+    Series { ids: vec![255, 256], timestamps: vec![] }.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::new(&encoded);
+    assert!(matches!(Small::deserialize(&mut deserializer), Err(SerbfError::Message(_))));
+
+    // So are over-long varints, in both kinds of fields
+    #[derive(Serialize)]
+    struct Malformed {
+        ids: Vec<u8>,
+        timestamps: Vec<u8>,
+    }
+
+    for malformed in [Malformed { ids: vec![0xff; 20], timestamps: vec![] }, Malformed { ids: vec![], timestamps: vec![0xff; 20] }] {
+        let mut serializer = SerbfSerializer::new();
+        malformed.serialize(&mut serializer).unwrap();
+        let encoded = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::new(&encoded);
+        assert_eq!(SerbfError::VarintOverflow.to_string(), Series::deserialize(&mut deserializer).unwrap_err().to_string());
+    }
+}
+
+#[test]
+fn annotated_dump() {
+    use serde::{Deserialize, Serialize};
+    use crate::schema::Schema;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Dot,
+        Line { from: (i32, i32), label: Option<String> },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Drawing {
+        id: u64,
+        shapes: Vec<Shape>,
+    }
+
+    let drawing = Drawing {
+        id: 42,
+        shapes: vec![Shape::Dot, Shape::Line { from: (-1, 2), label: Some("Halloea".to_string()) }],
+    };
+
+    let schema = Schema::trace::<Drawing>().unwrap();
+    let config = SerbfConfig { struct_encoding: StructEncoding::LengthPrefixed, checksum: Checksum::Crc32c, ..SerbfConfig::default() };
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    drawing.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut out = Vec::new();
+    crate::dump(&encoded, config, Some(&schema), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("00000000  1e                       Drawing len 30\n"));
+    assert!(out.contains("00000001  2a 00 00 00 00 00 00 00    id: u64 42\n"));
+    assert!(out.contains("    [1] Shape::Line\n"));
+    assert!(out.contains("          [0] i32 -1\n"));
+    assert!(out.contains("        label: some\n"));
+    assert!(out.contains("  07 48 61 6c 6c 6f 65 61            str \"Halloea\"\n"));
+    assert!(out.ends_with("checksum Crc32c ok\n"));
+
+    // The lines before an error are still written
+    let mut serializer = SerbfSerializer::new();
+    drawing.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut out = Vec::new();
+    assert!(crate::dump(&encoded[..12], SerbfConfig::default(), Some(&schema), &mut out).is_err());
+    assert!(String::from_utf8(out).unwrap().contains("id: u64"));
+
+    // Tags need no schema, the envelope header gets a line of its own
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(SerbfConfig::self_describing())).unwrap();
+    drawing.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut out = Vec::new();
+    crate::dump_with_envelope(&encoded, None, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.starts_with("00000000  53 52 42 46 01 01 00     envelope v1: "));
+    assert!(out.contains("  [0] key str \"id\"\n"));
+    assert!(out.contains("    [1] variant 1\n"));
+
+    // Without either, strings and varints are guessed
+    let mut out = Vec::new();
+    crate::dump(&[5, b'H', b'a', b'l', b'l', b'o', 0xac, 0x02, 0xff], SerbfConfig::default(), None, &mut out).unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "\
+00000000  05 48 61 6c 6c 6f        str? \"Hallo\"
+00000006  ac 02                    varint? 300
+00000008  ff                       u8? 255
+");
+
+    // Encoding by schema is the inverse of decoding and takes values as parsed from JSON
+    let mut serializer = SerbfSerializer::with_config(config);
+    drawing.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&encoded, config).unwrap();
+
+    let value = schema.decode(&mut deserializer).unwrap();
+
+    let json = Value::Map(vec![
+        (Value::String("shapes".to_string()), Value::Seq(vec![
+            Value::String("Dot".to_string()),
+            Value::Map(vec![(Value::String("1".to_string()), Value::Map(vec![
+                (Value::String("from".to_string()), Value::Seq(vec![Value::I64(-1), Value::U64(2)])),
+                (Value::String("label".to_string()), Value::String("Halloea".to_string())),
+            ]))]),
+        ])),
+        (Value::String("id".to_string()), Value::String("42".to_string())),
+    ]);
+
+    for config in [config, SerbfConfig::self_describing(), SerbfConfig::canonical()] {
+        let mut expected = SerbfSerializer::with_config(config);
+        drawing.serialize(&mut expected).unwrap();
+        let expected = expected.into_inner();
+
+        for value in [&value, &json] {
+            let mut serializer = SerbfSerializer::with_config(config);
+            schema.encode(value, &mut serializer).unwrap();
+
+            assert_eq!(serializer.into_inner(), expected);
+        }
+    }
+
+    let mut serializer = SerbfSerializer::new();
+    let res = schema.encode(&Value::Map(vec![(Value::String("id".to_string()), Value::I8(-1))]), &mut serializer);
+    assert!(matches!(res, Err(SerbfError::Schema(_))));
+}
+
+#[test]
+fn transcoding() {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use crate::schema::Schema;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Status {
+        Open,
+        Held(String),
+        Shipped { at: u64 },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        items: Vec<(char, f64)>,
+        status: Vec<Status>,
+        note: Option<String>,
+        stock: BTreeMap<u8, i16>,
+        raw: Vec<u8>,
+    }
+
+    let order = Order {
+        id: 7,
+        items: vec![('a', 0.5), ('b', -2.0)],
+        status: vec![Status::Open, Status::Held("Halloea".to_string()), Status::Shipped { at: 5 }],
+        note: None,
+        stock: BTreeMap::from([(1, -3), (200, 4)]),
+        raw: vec![1, 2],
+    };
+
+    let schema = Schema::trace::<Order>().unwrap();
+    let expected = serde_json::to_value(&order).unwrap();
+
+    let configs = [
+        SerbfConfig::default(),
+        SerbfConfig { int_encoding: IntEncoding::Varint, struct_encoding: StructEncoding::LengthPrefixed, ..SerbfConfig::default() },
+        SerbfConfig::self_describing(),
+    ];
+
+    for config in configs {
+        let mut serializer = SerbfSerializer::with_config(config);
+        order.serialize(&mut serializer).unwrap();
+        let encoded = serializer.into_inner();
+
+        // Schema-guided output is the same as serde_json's for the typed value
+        let mut deserializer = SerbfDeserializer::with_config(&encoded, config).unwrap();
+        let json = crate::transcode_with_schema(&mut deserializer, &schema, serde_json::value::Serializer).unwrap();
+
+        if !config.self_describing {
+            assert_eq!(json, expected);
+        }
+
+        // And goes back to the same bytes, through JSON text
+        let mut serializer = SerbfSerializer::with_config(config);
+        let text = serde_json::to_string(&json).unwrap();
+        crate::transcode_from(&mut serde_json::Deserializer::from_str(&text), Some(&schema), &mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), encoded);
+
+        // Anything after the value is left for the caller to reject
+        assert!(deserializer.end().is_ok());
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+
+        let mut deserializer = SerbfDeserializer::with_config(&trailing, config).unwrap();
+        crate::transcode_with_schema(&mut deserializer, &schema, serde_json::value::Serializer).unwrap();
+        assert!(matches!(deserializer.end(), Err(SerbfError::TrailingData(1))));
+    }
+
+    // Tags only carry variant indices
+    let mut serializer = SerbfSerializer::with_config(SerbfConfig::self_describing());
+    order.serialize(&mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let mut deserializer = SerbfDeserializer::with_config(&encoded, SerbfConfig::self_describing()).unwrap();
+    let json = crate::transcode(&mut deserializer, serde_json::value::Serializer).unwrap();
+
+    assert_eq!(json["status"], serde_json::json!([{ "0": null }, { "1": "Halloea" }, { "2": { "at": 5 } }]));
+    assert_eq!(json["items"], expected["items"]);
+
+    let mut deserializer = SerbfDeserializer::new(&[]);
+    assert!(crate::transcode(&mut deserializer, serde_json::value::Serializer).is_err());
+}
+
+#[test]
+fn bincode_compat() {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use crate::schema::Schema;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect(u16, u16),
+        Named { id: u64, label: String },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Point(i32, i32);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        tag: char,
+        wide: char,
+        flags: (bool, i8),
+        note: Option<String>,
+        missing: Option<u8>,
+        at: Point,
+        items: Vec<u16>,
+        attrs: BTreeMap<String, i64>,
+        shapes: Vec<Shape>,
+        big: i128,
+        unit: (),
+    }
+
+    let order = Order {
+        id: 7,
+        tag: 'x',
+        wide: 'é',
+        flags: (true, -2),
+        note: Some("hi".to_string()),
+        missing: None,
+        at: Point(-1, 2),
+        items: vec![1, 300],
+        attrs: BTreeMap::from([("a".to_string(), -1), ("bb".to_string(), 300)]),
+        shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect(3, 4), Shape::Named { id: 9, label: "z".to_string() }],
+        big: -5,
+        unit: (),
+    };
+
+    // Recorded from bincode 1.3.3 `bincode::serialize(&order)`
+    let recorded = [
+        7, 0, 0, 0,
+        120,
+        195, 169,
+        1, 254,
+        1, 2, 0, 0, 0, 0, 0, 0, 0, 104, 105,
+        0,
+        255, 255, 255, 255, 2, 0, 0, 0,
+        2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 44, 1,
+        2, 0, 0, 0, 0, 0, 0, 0,
+        1, 0, 0, 0, 0, 0, 0, 0, 97, 255, 255, 255, 255, 255, 255, 255, 255,
+        2, 0, 0, 0, 0, 0, 0, 0, 98, 98, 44, 1, 0, 0, 0, 0, 0, 0,
+        4, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0,
+        1, 0, 0, 0, 0, 0, 192, 63,
+        2, 0, 0, 0, 3, 0, 4, 0,
+        3, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 122,
+        251, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
+    ];
+
+    let config = SerbfConfig::bincode();
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    order.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), recorded);
+
+    let mut deserializer = SerbfDeserializer::with_config(&recorded, config).unwrap();
+    assert_eq!(order, Order::deserialize(&mut deserializer).unwrap());
+
+    // Schema-driven decoding follows the same layout
+    let schema = Schema::trace::<Order>().unwrap();
+
+    let mut deserializer = SerbfDeserializer::with_config(&recorded, config).unwrap();
+    let value = schema.decode(&mut deserializer).unwrap();
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    schema.encode(&value, &mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), recorded);
+
+    // The envelope keeps the mode, its header comes before the bincode bytes
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config)).unwrap();
+    order.serialize(&mut serializer).unwrap();
+    let enveloped = serializer.into_inner();
+
+    let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&enveloped).unwrap();
+    assert_eq!(envelope.config.compat, Compat::Bincode);
+    assert!(enveloped.ends_with(&recorded));
+    assert_eq!(order, Order::deserialize(&mut deserializer).unwrap());
+
+    // A char's length comes from its lead byte
+    let mut deserializer = SerbfDeserializer::with_config(&[0xff], config).unwrap();
+    assert!(matches!(char::deserialize(&mut deserializer), Err(SerbfError::InvalidChar)));
+}
+
+#[test]
+fn postcard_compat() {
+    use std::collections::BTreeMap;
+    use serde::{Deserialize, Serialize};
+    use crate::schema::Schema;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect(u16, u16),
+        Named { id: u64, label: String },
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Point(i32, i32);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        tag: char,
+        wide: char,
+        flags: (bool, i8),
+        note: Option<String>,
+        missing: Option<u8>,
+        at: Point,
+        items: Vec<u16>,
+        attrs: BTreeMap<String, i64>,
+        shapes: Vec<Shape>,
+        big: i128,
+        unit: (),
+    }
+
+    let order = Order {
+        id: 7,
+        tag: 'x',
+        wide: 'é',
+        flags: (true, -2),
+        note: Some("hi".to_string()),
+        missing: None,
+        at: Point(-1, 2),
+        items: vec![1, 300],
+        attrs: BTreeMap::from([("a".to_string(), -1), ("bb".to_string(), 300)]),
+        shapes: vec![Shape::Empty, Shape::Circle(1.5), Shape::Rect(3, 4), Shape::Named { id: 9, label: "z".to_string() }],
+        big: -5,
+        unit: (),
+    };
+
+    // Recorded from postcard 1.1.3 `postcard::to_stdvec(&order)`
+    let recorded = [
+        7,
+        1, 120,
+        2, 195, 169,
+        1, 254,
+        1, 2, 104, 105,
+        0,
+        1, 4,
+        2, 1, 172, 2,
+        2, 1, 97, 1, 2, 98, 98, 216, 4,
+        4, 0, 1, 0, 0, 192, 63, 2, 3, 4, 3, 9, 1, 122,
+        9,
+    ];
+
+    let config = SerbfConfig::postcard();
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    order.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), recorded);
+
+    let mut deserializer = SerbfDeserializer::with_config(&recorded, config).unwrap();
+    assert_eq!(order, Order::deserialize(&mut deserializer).unwrap());
+
+    let schema = Schema::trace::<Order>().unwrap();
+
+    let mut deserializer = SerbfDeserializer::with_config(&recorded, config).unwrap();
+    let value = schema.decode(&mut deserializer).unwrap();
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    schema.encode(&value, &mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), recorded);
+
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config)).unwrap();
+    order.serialize(&mut serializer).unwrap();
+    let enveloped = serializer.into_inner();
+
+    let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&enveloped).unwrap();
+    assert_eq!(envelope.config, config);
+    assert_eq!(order, Order::deserialize(&mut deserializer).unwrap());
+
+    // Integer extremes, nested options and multi-byte UTF-8
+    type Edges = (i16, i32, i64, u64, u128, i128, f64, char, Vec<(u8, i32)>, Option<Option<u16>>, String);
+
+    let edges: Edges = (i16::MIN, i32::MAX, i64::MIN, u64::MAX, u128::MAX, i128::MIN, 1.0, 'ß', vec![(1, -1)], Some(Some(0)), "ünï".to_string());
+
+    let recorded = [
+        255, 255, 3,
+        254, 255, 255, 255, 15,
+        255, 255, 255, 255, 255, 255, 255, 255, 255, 1,
+        255, 255, 255, 255, 255, 255, 255, 255, 255, 1,
+        255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 3,
+        255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 3,
+        0, 0, 0, 0, 0, 0, 240, 63,
+        2, 195, 159,
+        1, 1, 1,
+        1, 1, 0,
+        5, 195, 188, 110, 195, 175,
+    ];
+
+    let mut serializer = SerbfSerializer::with_config(config);
+    edges.serialize(&mut serializer).unwrap();
+    assert_eq!(serializer.into_inner(), recorded);
+
+    let mut deserializer = SerbfDeserializer::with_config(&recorded, config).unwrap();
+    assert_eq!(edges, Edges::deserialize(&mut deserializer).unwrap());
+}
+
+#[test]
+fn char_encoding() {
+    use serde::{Deserialize, Serialize};
+    use crate::value::Value;
+
+    let chars = ['a', 'é', '€', '\u{10ffff}'];
+
+    let encodings = [
+        (SerbfConfig::default(), vec![2, 0xc3, 0xa9]),
+        (SerbfConfig { char_encoding: CharEncoding::Utf8, ..SerbfConfig::default() }, vec![0xc3, 0xa9]),
+        (SerbfConfig { char_encoding: CharEncoding::Scalar, ..SerbfConfig::default() }, vec![0xe9, 0, 0, 0]),
+        (SerbfConfig { char_encoding: CharEncoding::Scalar, int_encoding: IntEncoding::Varint, ..SerbfConfig::default() }, vec![0xe9, 1]),
+    ];
+
+    for (config, expected) in encodings {
+        let mut serializer = SerbfSerializer::with_config(config);
+        'é'.serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), expected);
+
+        let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(config)).unwrap();
+        chars.serialize(&mut serializer).unwrap();
+        let encoded = serializer.into_inner();
+
+        let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&encoded).unwrap();
+        assert_eq!(envelope.config.char_encoding, config.char_encoding);
+        assert_eq!(chars, <[char; 4]>::deserialize(&mut deserializer).unwrap());
+
+        // Tagged chars decode without a type hint
+        let config = SerbfConfig { self_describing: true, ..config };
+
+        let mut serializer = SerbfSerializer::with_config(config);
+        'é'.serialize(&mut serializer).unwrap();
+        let encoded = serializer.into_inner();
+
+        let mut deserializer = SerbfDeserializer::with_config(&encoded, config).unwrap();
+        assert_eq!(Value::deserialize(&mut deserializer).unwrap(), Value::Char('é'));
+    }
+
+    // Anything but exactly one scalar value is rejected
+    let invalid = [
+        (SerbfConfig::default(), vec![0]),
+        (SerbfConfig::default(), vec![2, b'a', b'b']),
+        (SerbfConfig::default(), vec![1, 0xc3]),
+        (SerbfConfig { char_encoding: CharEncoding::Utf8, ..SerbfConfig::default() }, vec![0xc3, b'a']),
+        (SerbfConfig { char_encoding: CharEncoding::Utf8, ..SerbfConfig::default() }, vec![0x80]),
+        (SerbfConfig { char_encoding: CharEncoding::Scalar, ..SerbfConfig::default() }, vec![0x00, 0xd8, 0, 0]),
+        (SerbfConfig { char_encoding: CharEncoding::Scalar, ..SerbfConfig::default() }, vec![0, 0, 0x11, 0]),
+    ];
+
+    for (config, data) in invalid {
+        let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+        assert!(matches!(char::deserialize(&mut deserializer), Err(SerbfError::InvalidChar)), "{:?}", data);
+    }
+}
+
+#[test]
+fn strict() {
+    use serde::Deserialize;
+
+    fn decode<'a, T: Deserialize<'a>>(data: &'a [u8], config: SerbfConfig) -> Result<T, SerbfError> {
+        let mut deserializer = SerbfDeserializer::with_config(data, config).unwrap();
+        T::deserialize(&mut deserializer)
+    }
+
+    let config = SerbfConfig::default();
+
+    assert!(matches!(decode::<bool>(&[2], config), Err(SerbfError::InvalidBool(2))));
+    assert!(matches!(decode::<Option<u8>>(&[7, 1], config), Err(SerbfError::InvalidOptionTag(7))));
+
+    // Varints that don't fit their type are always rejected
+    let config = SerbfConfig { int_encoding: IntEncoding::Varint, ..SerbfConfig::default() };
+
+    assert_eq!(decode::<u16>(&[0xff, 0xff, 0x03], config).unwrap(), u16::MAX);
+    assert!(matches!(decode::<u16>(&[0x80, 0x80, 0x04], config), Err(SerbfError::VarintOverflow)));
+    assert!(matches!(decode::<u32>(&[0xff, 0xff, 0xff, 0xff, 0x1f], config), Err(SerbfError::VarintOverflow)));
+    assert!(matches!(decode::<i64>(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00], config), Err(SerbfError::VarintOverflow)));
+    assert!(matches!(decode::<Vec<u8>>(&[0xff; 11], config), Err(SerbfError::VarintOverflow)));
+
+    // Padded ones only in strict mode
+    let strict = SerbfConfig { strict: true, ..config };
+
+    assert_eq!(decode::<u32>(&[0x80, 0x00], config).unwrap(), 0);
+    assert!(matches!(decode::<u32>(&[0x80, 0x00], strict), Err(SerbfError::NonCanonicalVarint)));
+    assert!(matches!(decode::<i32>(&[0x83, 0x80, 0x00], strict), Err(SerbfError::NonCanonicalVarint)));
+    assert!(matches!(decode::<Vec<u8>>(&[0x81, 0x00, 9], strict), Err(SerbfError::NonCanonicalVarint)));
+    assert_eq!(decode::<Vec<u8>>(&[0x81, 0x00, 9], config).unwrap(), [9]);
+    assert_eq!(decode::<(u32, i32)>(&[2, 0x80, 0x01, 0x03], strict).unwrap(), (128, -2));
+
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(strict)).unwrap();
+    serde::Serialize::serialize(&300u32, &mut serializer).unwrap();
+    let encoded = serializer.into_inner();
+
+    let (mut deserializer, envelope) = SerbfDeserializer::with_envelope(&encoded).unwrap();
+    assert!(envelope.config.strict);
+    assert_eq!(u32::deserialize(&mut deserializer).unwrap(), 300);
+}
+
+#[test]
+fn length_overflow() {
+    use serde::Deserialize;
+
+    // A length of 2^32, as a varint and as a bincode u64
+    let lengths = [
+        (SerbfConfig::default(), vec![0x80, 0x80, 0x80, 0x80, 0x10]),
+        (SerbfConfig::bincode(), vec![0, 0, 0, 0, 1, 0, 0, 0]),
+    ];
+
+    for (config, data) in lengths {
+        let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+        let res = <&[u8]>::deserialize(&mut deserializer);
+
+        // Fits a 64-bit usize, so decoding only runs out of data
+        match usize::BITS {
+            64 => { assert!(matches!(res, Err(SerbfError::IOError(_)))) }
+            _ => { assert!(matches!(res, Err(SerbfError::LengthOverflow(0x1_0000_0000)))) }
+        }
+    }
+
+    // What a 32-bit target does with the same length
+    assert!(matches!(crate::de::narrow_len::<u32>(0x1_0000_0000), Err(SerbfError::LengthOverflow(0x1_0000_0000))));
+    assert_eq!(u32::MAX, crate::de::narrow_len::<u32>(0xffff_ffff).unwrap());
+
+    // Lengths past u64 are rejected on every target
+    let mut data = vec![0xff; 9];
+    data.push(0x02);
+
+    let mut deserializer = SerbfDeserializer::with_config(&data, SerbfConfig::default()).unwrap();
+    assert!(matches!(String::deserialize(&mut deserializer), Err(SerbfError::VarintOverflow)));
+
+    let mut data = vec![0xff; 9];
+    data.push(0x01);
+
+    let mut deserializer = SerbfDeserializer::with_config(&data, SerbfConfig::default()).unwrap();
+    assert!(matches!(String::deserialize(&mut deserializer), Err(SerbfError::IOError(_))));
+}
+
+#[test]
+fn recursion_limit() {
+    use serde::Deserialize;
+    use crate::schema::Schema;
+
+    // Every `Some` and single element sequence header is a byte that nests one level deeper
+    let nested = |header: &[u8], depth: usize| {
+        let mut data = header.repeat(depth);
+        data.push(0);
+        data
+    };
+
+    let config = SerbfConfig::self_describing();
+
+    for header in [&[18][..], &[19, 1], &[20, 1, 0], &[21, 0]] {
+        let data = nested(header, 128);
+        let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+        assert!(Value::deserialize(&mut deserializer).is_ok());
+
+        let data = nested(header, 100_000);
+        let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+        assert!(matches!(Value::deserialize(&mut deserializer), Err(SerbfError::RecursionLimit(128))));
+    }
+
+    // Recursive types nest just as cheaply in compact mode
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    enum List {
+        Nil,
+        Cons(Box<List>),
+    }
+
+    let data = nested(&[1], 100_000);
+    let mut deserializer = SerbfDeserializer::new(&data);
+    assert!(matches!(List::deserialize(&mut deserializer), Err(SerbfError::RecursionLimit(128))));
+
+    let data = nested(&[1], 100_000);
+    let mut deserializer = SerbfDeserializer::new(&data);
+    assert!(matches!(<Option<Box<List>>>::deserialize(&mut deserializer), Err(SerbfError::RecursionLimit(128))));
+
+    let schema = Schema::trace::<List>().unwrap();
+
+    let data = nested(&[1], 128);
+    let mut deserializer = SerbfDeserializer::new(&data);
+    assert!(schema.decode(&mut deserializer).is_ok());
+
+    let data = nested(&[1], 100_000);
+    let mut deserializer = SerbfDeserializer::new(&data);
+    assert!(matches!(schema.decode(&mut deserializer), Err(SerbfError::RecursionLimit(128))));
+
+    assert!(matches!(crate::dump(&data, SerbfConfig::default(), Some(&schema), &mut Vec::new()), Err(SerbfError::RecursionLimit(128))));
+
+    let data = nested(&[18], 128);
+    assert!(crate::dump(&data, config, None, &mut Vec::new()).is_ok());
+
+    let data = nested(&[18], 100_000);
+    assert!(matches!(crate::dump(&data, config, None, &mut Vec::new()), Err(SerbfError::RecursionLimit(128))));
+
+    let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+    let err = crate::transcode(&mut deserializer, serde_json::value::Serializer).unwrap_err();
+    assert_eq!(err.to_string(), SerbfError::RecursionLimit(128).to_string());
+
+    let data = nested(&[18], 128);
+    let mut deserializer = SerbfDeserializer::with_config(&data, config).unwrap();
+    assert!(crate::transcode(&mut deserializer, serde_json::value::Serializer).is_ok());
+
+    let data = nested(&[1], 100_000);
+    let mut deserializer = SerbfDeserializer::new(&data);
+    let err = crate::transcode_with_schema(&mut deserializer, &schema, serde_json::value::Serializer).unwrap_err();
+    assert_eq!(err.to_string(), SerbfError::RecursionLimit(128).to_string());
+}
+
+#[test]
+#[cfg(any(feature = "chacha20poly1305", feature = "aes-gcm"))]
+fn seal() {
+    use serde::{Deserialize, Serialize};
+    use crate::seal::{generate_key, Cipher, Sealer};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Session {
+        user: String,
+        expires: u64,
+    }
+
+    let ciphers = [
+        #[cfg(feature = "chacha20poly1305")]
+        Cipher::ChaCha20Poly1305,
+        #[cfg(feature = "aes-gcm")]
+        Cipher::Aes256Gcm,
+    ];
+
+    let session = Session { user: "Halloea Friend".to_string(), expires: 1_700_000_000 };
+
+    for cipher in ciphers {
+        let sealer = Sealer::new(cipher, generate_key());
+        let envelope = Envelope::new(SerbfConfig::default()).with_type_id(7);
+
+        let mut buf = sealer.seal_value(&envelope, &session).unwrap();
+
+        let (opened, header) = sealer.open_value::<Session>(&buf).unwrap();
+
+        assert_eq!(session, opened);
+        assert_eq!(Some(7), header.type_id);
+
+        // Fresh nonce on every seal
+        assert_ne!(buf, sealer.seal_value(&envelope, &session).unwrap());
+
+        // Wrong key
+        let other = Sealer::new(cipher, generate_key());
+        assert!(matches!(other.open_value::<Session>(&buf), Err(SerbfError::AuthenticationFailed)));
+
+        // The header is authenticated, so the type id can't be swapped
+        let mut swapped = Vec::new();
+        envelope.with_type_id(8).write(&mut swapped).unwrap();
+        swapped.extend_from_slice(&buf[swapped.len()..]);
+        assert!(matches!(sealer.open_value::<Session>(&swapped), Err(SerbfError::AuthenticationFailed)));
+
+        let last = buf.len() - 1;
+        buf[last] ^= 0x01;
+
+        assert!(matches!(sealer.open_value::<Session>(&buf), Err(SerbfError::AuthenticationFailed)));
+        assert!(matches!(sealer.open(&[], &[]), Err(SerbfError::IOError(_))));
+    }
+}
+
+#[test]
+#[cfg(feature = "mmap")]
+fn mmap() {
+    use serde::{Deserialize, Serialize};
+    use crate::mmap::MappedFile;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Entry<'a> {
+        name: &'a str,
+        data: &'a [u8],
+    }
+
+    let entries = (0..16).map(|_| Entry { name: "Halloea Friend", data: &[1, 2, 3] }).collect::<Vec<_>>();
+
+    let path = std::env::temp_dir().join(format!("serbf-mmap-{}.bin", std::process::id()));
+
+    let mut serializer = SerbfSerializer::with_envelope(&Envelope::new(SerbfConfig::default()).with_type_id(9)).unwrap();
+    entries.serialize(&mut serializer).unwrap();
+    std::fs::write(&path, serializer.into_inner()).unwrap();
+
+    let (file, envelope) = unsafe { MappedFile::open_with_envelope(&path) }.unwrap();
+
+    assert_eq!(Some(9), envelope.type_id);
+
+    let mapped = file.get::<Vec<Entry>>().unwrap();
+
+    assert_eq!(entries, mapped);
+
+    // The strings point into the mapping
+    let range = file.as_slice().as_ptr_range();
+    assert!(range.contains(&mapped[0].name.as_ptr()));
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,101 @@
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+use memmap2::Mmap;
+use serde::Deserialize;
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::envelope::Envelope;
+use crate::error::SerbfError;
+
+/// Memory-mapped serbf file. Values are deserialized straight from the mapping and may borrow
+/// `&str` and `&[u8]` from it for as long as the `MappedFile` lives.
+pub struct MappedFile {
+    mmap: Mmap,
+    config: SerbfConfig,
+
+    /// Where the payload starts, after the envelope header if there is one
+    start: usize,
+}
+
+impl MappedFile {
+    pub fn new(mmap: Mmap, config: SerbfConfig) -> Self {
+        Self {
+            mmap,
+            config,
+            start: 0,
+        }
+    }
+
+    /// Reads the envelope header from the mapping and decodes the payload with its config.
+    pub fn with_envelope(mmap: Mmap) -> Result<(Self, Envelope), SerbfError> {
+        let mut header = Cursor::new(&mmap[..]);
+
+        let envelope = Envelope::read(&mut header)?;
+        let start = header.position() as usize;
+
+        let file = Self {
+            mmap,
+            config: envelope.config,
+            start,
+        };
+
+        Ok((file, envelope))
+    }
+
+    /// Maps the file at `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped, see `memmap2::Mmap::map`.
+    pub unsafe fn open<P: AsRef<Path>>(path: P, config: SerbfConfig) -> Result<Self, SerbfError> {
+        Ok(Self::new(map(path.as_ref())?, config))
+    }
+
+    /// Maps the file at `path` and reads its envelope header.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped, see `memmap2::Mmap::map`.
+    pub unsafe fn open_with_envelope<P: AsRef<Path>>(path: P) -> Result<(Self, Envelope), SerbfError> {
+        Self::with_envelope(map(path.as_ref())?)
+    }
+
+    pub fn config(&self) -> &SerbfConfig {
+        &self.config
+    }
+
+    /// The whole mapping, header included.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Deserializer over the payload, verifying the checksum first if the config has one.
+    pub fn deserializer(&self) -> Result<SerbfDeserializer<'_>, SerbfError> {
+        match self.start {
+            0 => { SerbfDeserializer::with_config(&self.mmap, self.config) }
+            _ => { Ok(SerbfDeserializer::with_envelope(&self.mmap)?.0) }
+        }
+    }
+
+    /// Decodes the payload, borrowing from the mapping.
+    pub fn get<'a, T>(&'a self) -> Result<T, SerbfError>
+    where
+        T: Deserialize<'a>
+    {
+        T::deserialize(&mut self.deserializer()?)
+    }
+}
+
+unsafe fn map(path: &Path) -> Result<Mmap, SerbfError> {
+    let file = match File::open(path) {
+        Ok(v) => { v }
+        Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+    };
+
+    match Mmap::map(&file) {
+        Ok(v) => { Ok(v) }
+        Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+    }
+}
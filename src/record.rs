@@ -0,0 +1,196 @@
+use std::io;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use varint_rs::VarintWriter;
+use crate::config::SerbfConfig;
+use crate::de::SerbfDeserializer;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+/// Appends records to `W`, each one prefixed with its byte length as a varint.
+///
+/// A record carries the config's checksum trailer, so corruption is caught per record.
+pub struct RecordWriter<W> {
+    writer: W,
+    config: SerbfConfig,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(writer: W, config: SerbfConfig) -> Self {
+        Self {
+            writer,
+            config,
+        }
+    }
+
+    pub fn write<T>(&mut self, value: &T) -> Result<(), SerbfError>
+    where
+        T: ?Sized + Serialize
+    {
+        let mut serializer = SerbfSerializer::with_config(self.config);
+        value.serialize(&mut serializer)?;
+
+        let record = serializer.into_inner();
+
+        let res = self.writer.write_u64_varint(record.len() as u64)
+            .and_then(|_| self.writer.write_all(&record));
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<(), SerbfError> {
+        match self.writer.flush() {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records written by `RecordWriter` until EOF.
+///
+/// A record that fails to decode doesn't affect the ones after it. A truncated record ends
+/// the iteration with `SerbfError::TruncatedRecord`. Lengths are read a byte at a time, so
+/// unbuffered readers should be wrapped in a `BufReader`.
+pub struct RecordReader<R, T> {
+    reader: R,
+    config: SerbfConfig,
+    buf: Vec<u8>,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R: Read, T: DeserializeOwned> RecordReader<R, T> {
+    pub fn new(reader: R, config: SerbfConfig) -> Self {
+        Self {
+            reader,
+            config,
+            buf: Vec::new(),
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Skips up to `n` records without decoding them and returns how many were skipped.
+    pub fn skip_records(&mut self, n: usize) -> Result<usize, SerbfError> {
+        for skipped in 0..n {
+            let len = match self.read_len()? {
+                Some(v) => { v }
+                None => { return Ok(skipped) }
+            };
+
+            let copied = match io::copy(&mut (&mut self.reader).take(len), &mut io::sink()) {
+                Ok(v) => { v }
+                Err(e) => { return Err(self.fail(SerbfError::IOError(Arc::new(e)))) }
+            };
+
+            if copied < len {
+                return Err(self.fail(SerbfError::TruncatedRecord));
+            }
+        }
+
+        Ok(n)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn fail(&mut self, error: SerbfError) -> SerbfError {
+        self.done = true;
+        error
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, SerbfError> {
+        let mut byte = [0];
+
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => { return Ok(None) }
+                Ok(_) => { return Ok(Some(byte[0])) }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => { return Err(self.fail(SerbfError::IOError(Arc::new(e)))) }
+            }
+        }
+    }
+
+    /// Reads the length of the next record, `None` on a clean EOF between records.
+    fn read_len(&mut self) -> Result<Option<u64>, SerbfError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let mut len = 0u64;
+        let mut shift = 0;
+
+        loop {
+            let byte = match self.read_byte()? {
+                Some(v) => { v }
+                None if shift == 0 => { self.done = true; return Ok(None) }
+                None => { return Err(self.fail(SerbfError::TruncatedRecord)) }
+            };
+
+            if shift > 63 || (shift == 63 && byte & 0x7f > 1) {
+                return Err(self.fail(SerbfError::VarintOverflow));
+            }
+
+            len |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return Ok(Some(len));
+            }
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<T>, SerbfError> {
+        let len = match self.read_len()? {
+            Some(v) => { v }
+            None => { return Ok(None) }
+        };
+
+        self.buf.clear();
+
+        match (&mut self.reader).take(len).read_to_end(&mut self.buf) {
+            Ok(v) if (v as u64) < len => { return Err(self.fail(SerbfError::TruncatedRecord)) }
+            Ok(_) => {}
+            Err(e) => { return Err(self.fail(SerbfError::IOError(Arc::new(e)))) }
+        }
+
+        let mut deserializer = SerbfDeserializer::with_config(&self.buf, self.config)?;
+        let value = T::deserialize(&mut deserializer)?;
+
+        // The record's length covers exactly one value
+        deserializer.end()?;
+
+        Ok(Some(value))
+    }
+}
+
+impl<R: Read, T: DeserializeOwned> Iterator for RecordReader<R, T> {
+    type Item = Result<T, SerbfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        match self.skip_records(n) {
+            Ok(_) => { self.next() }
+            Err(e) => { Some(Err(e)) }
+        }
+    }
+}
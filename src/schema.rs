@@ -0,0 +1,1136 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
+use serde::de::{DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::de::SerbfDeserializer;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+use crate::value::Value;
+
+/// Machine-readable description of a Rust type, as seen by serde.
+///
+/// Named containers (structs and enums) are stored once in `types` and referenced
+/// through `Format::Named`, which also covers recursive types. The schema itself
+/// implements `Serialize`, so it can be emitted as JSON or in serbf encoding.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    pub root: Format,
+    pub types: BTreeMap<String, Container>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Format {
+    /// Never reached while tracing, e.g. the elements of a recursion-terminating empty seq.
+    Unknown,
+    Unit,
+    Bool,
+
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+
+    F32,
+    F64,
+
+    Char,
+    Str,
+    Bytes,
+
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    Tuple(Vec<Format>),
+    Map { key: Box<Format>, value: Box<Format> },
+    Named(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Container {
+    UnitStruct,
+    NewtypeStruct(Format),
+    TupleStruct(Vec<Format>),
+    Struct(Vec<Field>),
+    /// Variants in declaration order, so the position is the encoded variant index.
+    Enum(Vec<Variant>),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    pub format: Format,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub format: VariantFormat,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VariantFormat {
+    Unknown,
+    Unit,
+    Newtype(Format),
+    Tuple(Vec<Format>),
+    Struct(Vec<Field>),
+}
+
+impl Schema {
+    /// Traces `T` by driving its `Deserialize` impl with placeholder values.
+    ///
+    /// Every enum variant is visited, which may take several passes. Recursion is cut off by
+    /// choosing `None`, empty collections or a variant that isn't already being traced.
+    pub fn trace<T>() -> Result<Self, SerbfError>
+    where
+        T: Deserialize<'static>
+    {
+        let mut tracer = Tracer::default();
+        let mut root = Format::Unknown;
+
+        loop {
+            let explored = tracer.explored();
+
+            let mut format = Format::Unknown;
+
+            T::deserialize(TraceDeserializer { tracer: &mut tracer, format: &mut format })?;
+
+            root.merge(format);
+
+            if tracer.complete() || tracer.explored() == explored {
+                break;
+            }
+        }
+
+        Ok(Self {
+            root,
+            types: tracer.types,
+        })
+    }
+
+    pub fn container(&self, name: &str) -> Option<&Container> {
+        self.types.get(name)
+    }
+
+    /// Decodes a value described by this schema, without its Rust type.
+    ///
+    /// Structs become maps keyed by field name, enums become `Value::Variant`.
+    pub fn decode(&self, deserializer: &mut SerbfDeserializer) -> Result<Value, SerbfError> {
+        // Self-describing data already carries everything we could add
+        if deserializer.config().self_describing {
+            return Value::deserialize(deserializer);
+        }
+
+        self.decode_format(&self.root, deserializer)
+    }
+
+    /// Decodes a value one nesting level deeper, see `SerbfDeserializer::enter`.
+    fn decode_nested(&self, format: &Format, de: &mut SerbfDeserializer) -> Result<Value, SerbfError> {
+        de.enter()?;
+        let res = self.decode_format(format, de);
+        de.leave();
+
+        res
+    }
+
+    fn decode_format(&self, format: &Format, de: &mut SerbfDeserializer) -> Result<Value, SerbfError> {
+        let value = match format {
+            Format::Unknown => { return Err(SerbfError::Schema("Can't decode a value of unknown format".to_string())) }
+            Format::Unit => { Value::Unit }
+            Format::Bool => { Value::Bool(de.read_bool()?) }
+            Format::I8 => { Value::I8(de.read_i8()?) }
+            Format::I16 => { Value::I16(de.read_i16()?) }
+            Format::I32 => { Value::I32(de.read_i32()?) }
+            Format::I64 => { Value::I64(de.read_i64()?) }
+            Format::I128 => { Value::I128(de.read_i128()?) }
+            Format::U8 => { Value::U8(de.read_u8()?) }
+            Format::U16 => { Value::U16(de.read_u16()?) }
+            Format::U32 => { Value::U32(de.read_u32()?) }
+            Format::U64 => { Value::U64(de.read_u64()?) }
+            Format::U128 => { Value::U128(de.read_u128()?) }
+            Format::F32 => { Value::F32(de.read_f32()?) }
+            Format::F64 => { Value::F64(de.read_f64()?) }
+            Format::Char => { Value::Char(de.read_char()?) }
+            Format::Str => { Value::String(de.read_str()?.to_string()) }
+            Format::Bytes => {
+                let len = de.read_len()?;
+
+                Value::Bytes(de.read_slice(len)?.to_vec())
+            }
+            Format::Option(v) => {
+                match de.read_option_tag()? {
+                    false => { Value::Option(None) }
+                    true => { Value::Option(Some(Box::new(self.decode_nested(v, de)?))) }
+                }
+            }
+            Format::Seq(v) => {
+                let len = de.read_len()?;
+
+                let mut vec = Vec::with_capacity(len.min(4096));
+
+                for _ in 0..len {
+                    vec.push(self.decode_nested(v, de)?);
+                }
+
+                Value::Seq(vec)
+            }
+            Format::Tuple(v) => { self.decode_tuple(v, de)? }
+            Format::Map { key, value } => {
+                let len = de.read_len()?;
+
+                let mut vec = Vec::with_capacity(len.min(4096));
+
+                for _ in 0..len {
+                    vec.push((self.decode_nested(key, de)?, self.decode_nested(value, de)?));
+                }
+
+                Value::Map(vec)
+            }
+            Format::Named(name) => {
+                let container = match self.container(name) {
+                    Some(v) => { v }
+                    None => { return Err(SerbfError::Schema(format!("Missing definition of {}", name))) }
+                };
+
+                match container {
+                    Container::UnitStruct => { Value::Unit }
+                    Container::NewtypeStruct(v) => { self.decode_nested(v, de)? }
+                    Container::TupleStruct(v) => { self.decode_tuple(v, de)? }
+                    Container::Struct(v) => { self.decode_fields(v, de)? }
+                    Container::Enum(v) => {
+                        let index = de.read_variant_index()?;
+
+                        let variant = match v.get(index as usize) {
+                            Some(v) => { v }
+                            None => { return Err(SerbfError::InvalidEnumID) }
+                        };
+
+                        let value = match &variant.format {
+                            VariantFormat::Unknown => { return Err(SerbfError::Schema(format!("Unknown format of variant {}::{}", name, variant.name))) }
+                            VariantFormat::Unit => { Value::Unit }
+                            VariantFormat::Newtype(v) => { self.decode_nested(v, de)? }
+                            VariantFormat::Tuple(v) => { self.decode_tuple(v, de)? }
+                            VariantFormat::Struct(v) => { self.decode_fields(v, de)? }
+                        };
+
+                        Value::Variant(index, Box::new(value))
+                    }
+                }
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn decode_tuple(&self, formats: &[Format], de: &mut SerbfDeserializer) -> Result<Value, SerbfError> {
+        let len = de.read_tuple_len(formats.len())?;
+
+        if len != formats.len() {
+            return Err(SerbfError::Schema(format!("Expected a tuple of {} elements, found {}", formats.len(), len)));
+        }
+
+        let mut vec = Vec::with_capacity(len);
+
+        for format in formats {
+            vec.push(self.decode_nested(format, de)?);
+        }
+
+        Ok(Value::Seq(vec))
+    }
+
+    fn decode_fields(&self, fields: &[Field], de: &mut SerbfDeserializer) -> Result<Value, SerbfError> {
+        let end = de.read_struct_end()?;
+
+        let mut vec = Vec::with_capacity(fields.len());
+
+        for field in fields {
+            if !de.has_struct_fields(end) {
+                break;
+            }
+
+            vec.push((Value::String(field.name.clone()), self.decode_nested(&field.format, de)?));
+        }
+
+        de.finish_struct(end)?;
+
+        Ok(Value::Map(vec))
+    }
+
+    /// Encodes a value described by this schema, the inverse of `decode`.
+    ///
+    /// Values are converted to the described types where that's lossless, so data parsed from
+    /// JSON can be encoded: integers of any width or as strings become the described integer,
+    /// structs may be maps keyed by field name with `None` options left out, and enums may be
+    /// maps keyed by variant name or index, or just the name of a unit variant.
+    pub fn encode<W: Write>(&self, value: &Value, serializer: &mut SerbfSerializer<W>) -> Result<(), SerbfError> {
+        self.encode_format(&self.root, value, serializer)
+    }
+
+    fn encode_format<W: Write>(&self, format: &Format, value: &Value, ser: &mut SerbfSerializer<W>) -> Result<(), SerbfError> {
+        match format {
+            Format::Unknown => { Err(SerbfError::Schema("Can't encode a value of unknown format".to_string())) }
+            Format::Unit => {
+                match value {
+                    Value::Unit => { ser.serialize_unit() }
+                    v => { Err(mismatch("unit", v)) }
+                }
+            }
+            Format::Bool => {
+                match value {
+                    Value::Bool(v) => { ser.serialize_bool(*v) }
+                    v => { Err(mismatch("bool", v)) }
+                }
+            }
+            Format::I8 => { ser.serialize_i8(int(value)?) }
+            Format::I16 => { ser.serialize_i16(int(value)?) }
+            Format::I32 => { ser.serialize_i32(int(value)?) }
+            Format::I64 => { ser.serialize_i64(int(value)?) }
+            Format::I128 => { ser.serialize_i128(int(value)?) }
+            Format::U8 => { ser.serialize_u8(int(value)?) }
+            Format::U16 => { ser.serialize_u16(int(value)?) }
+            Format::U32 => { ser.serialize_u32(int(value)?) }
+            Format::U64 => { ser.serialize_u64(int(value)?) }
+            Format::U128 => {
+                match value {
+                    Value::U128(v) => { ser.serialize_u128(*v) }
+                    v => { ser.serialize_u128(int(v)?) }
+                }
+            }
+            Format::F32 => {
+                match value {
+                    Value::F32(v) => { ser.serialize_f32(*v) }
+                    v => { ser.serialize_f32(float(v)? as f32) }
+                }
+            }
+            Format::F64 => { ser.serialize_f64(float(value)?) }
+            Format::Char => {
+                let mut chars = match value {
+                    Value::Char(v) => { return ser.serialize_char(*v) }
+                    Value::String(v) => { v.chars() }
+                    v => { return Err(mismatch("char", v)) }
+                };
+
+                match (chars.next(), chars.next()) {
+                    (Some(v), None) => { ser.serialize_char(v) }
+                    _ => { Err(SerbfError::InvalidChar) }
+                }
+            }
+            Format::Str => {
+                match value {
+                    Value::String(v) => { ser.serialize_str(v) }
+                    Value::Char(v) => { ser.serialize_str(&v.to_string()) }
+                    v => { Err(mismatch("string", v)) }
+                }
+            }
+            Format::Bytes => {
+                match value {
+                    Value::Bytes(v) => { ser.serialize_bytes(v) }
+                    Value::Seq(v) => { ser.serialize_bytes(&v.iter().map(int).collect::<Result<Vec<u8>, _>>()?) }
+                    v => { Err(mismatch("bytes", v)) }
+                }
+            }
+            Format::Option(format) => {
+                match value {
+                    Value::Option(None) | Value::Unit => { ser.serialize_none() }
+                    Value::Option(Some(v)) => { ser.write_some()?; self.encode_format(format, v, ser) }
+                    v => { ser.write_some()?; self.encode_format(format, v, ser) }
+                }
+            }
+            Format::Seq(format) => {
+                let elements = elements(value)?;
+
+                ser.serialize_seq(Some(elements.len()))?;
+
+                for v in elements.iter() {
+                    self.encode_format(format, v, ser)?;
+                }
+
+                SerializeSeq::end(ser)
+            }
+            Format::Tuple(formats) => { self.encode_tuple(formats, value, ser) }
+            Format::Map { key, value: value_format } => {
+                let entries = match value {
+                    Value::Map(v) => { v }
+                    v => { return Err(mismatch("map", v)) }
+                };
+
+                ser.serialize_map(Some(entries.len()))?;
+
+                for (k, v) in entries {
+                    ser.start_map_key();
+                    self.encode_format(key, k, ser)?;
+
+                    ser.start_map_value();
+                    self.encode_format(value_format, v, ser)?;
+                }
+
+                SerializeMap::end(ser)
+            }
+            Format::Named(name) => {
+                let container = match self.container(name) {
+                    Some(v) => { v }
+                    None => { return Err(SerbfError::Schema(format!("Missing definition of {}", name))) }
+                };
+
+                match container {
+                    Container::UnitStruct => { ser.serialize_unit() }
+                    Container::NewtypeStruct(v) => { self.encode_format(v, value, ser) }
+                    Container::TupleStruct(v) => { self.encode_tuple(v, value, ser) }
+                    Container::Struct(v) => { self.encode_fields(v, value, ser) }
+                    Container::Enum(v) => {
+                        let (index, value) = find_variant(v, value)?;
+
+                        let variant = match v.get(index as usize) {
+                            Some(v) => { v }
+                            None => { return Err(SerbfError::InvalidEnumID) }
+                        };
+
+                        ser.write_variant_index(index)?;
+
+                        match &variant.format {
+                            VariantFormat::Unknown => { Err(SerbfError::Schema(format!("Unknown format of variant {}::{}", name, variant.name))) }
+                            VariantFormat::Unit => { ser.serialize_unit() }
+                            VariantFormat::Newtype(v) => { self.encode_format(v, value, ser) }
+                            VariantFormat::Tuple(v) => { self.encode_tuple(v, value, ser) }
+                            VariantFormat::Struct(v) => { self.encode_fields(v, value, ser) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode_tuple<W: Write>(&self, formats: &[Format], value: &Value, ser: &mut SerbfSerializer<W>) -> Result<(), SerbfError> {
+        let elements = elements(value)?;
+
+        if elements.len() != formats.len() {
+            return Err(SerbfError::Schema(format!("Expected a tuple of {} elements, found {}", formats.len(), elements.len())));
+        }
+
+        ser.serialize_tuple(formats.len())?;
+
+        for (format, v) in formats.iter().zip(elements.iter()) {
+            self.encode_format(format, v, ser)?;
+        }
+
+        SerializeTuple::end(ser)
+    }
+
+    fn encode_fields<W: Write>(&self, fields: &[Field], value: &Value, ser: &mut SerbfSerializer<W>) -> Result<(), SerbfError> {
+        ser.serialize_struct("", fields.len())?;
+
+        for (i, field) in fields.iter().enumerate() {
+            let v = match value {
+                Value::Map(entries) => {
+                    entries.iter().find(|(k, _)| matches!(k, Value::String(v) if *v == field.name)).map(|(_, v)| v)
+                }
+                Value::Seq(elements) if elements.len() == fields.len() => { elements.get(i) }
+                v => { return Err(mismatch("struct", v)) }
+            };
+
+            let v = match (v, &field.format) {
+                (Some(v), _) => { v }
+                (None, Format::Option(_)) => { &Value::Option(None) }
+                (None, _) => { return Err(SerbfError::Schema(format!("Missing field {}", field.name))) }
+            };
+
+            // Same as `SerializeStruct::serialize_field`, which only takes static names
+            if ser.config().self_describing {
+                ser.serialize_str(&field.name)?;
+            }
+
+            self.encode_format(&field.format, v, ser)?;
+        }
+
+        SerializeStruct::end(ser)
+    }
+}
+
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Unit => { "unit" }
+        Value::Bool(_) => { "bool" }
+        Value::I8(_) | Value::I16(_) | Value::I32(_) | Value::I64(_) | Value::I128(_) => { "signed integer" }
+        Value::U8(_) | Value::U16(_) | Value::U32(_) | Value::U64(_) | Value::U128(_) => { "unsigned integer" }
+        Value::F32(_) | Value::F64(_) => { "float" }
+        Value::Char(_) => { "char" }
+        Value::String(_) => { "string" }
+        Value::Bytes(_) => { "bytes" }
+        Value::Option(_) => { "option" }
+        Value::Seq(_) => { "seq" }
+        Value::Map(_) => { "map" }
+        Value::Variant(..) => { "variant" }
+    }
+}
+
+fn mismatch(expected: &str, value: &Value) -> SerbfError {
+    SerbfError::Schema(format!("Expected {}, found {}", expected, describe(value)))
+}
+
+fn int<T: TryFrom<i128>>(value: &Value) -> Result<T, SerbfError> {
+    let v = match value {
+        Value::I8(v) => { *v as i128 }
+        Value::I16(v) => { *v as i128 }
+        Value::I32(v) => { *v as i128 }
+        Value::I64(v) => { *v as i128 }
+        Value::I128(v) => { *v }
+        Value::U8(v) => { *v as i128 }
+        Value::U16(v) => { *v as i128 }
+        Value::U32(v) => { *v as i128 }
+        Value::U64(v) => { *v as i128 }
+        Value::U128(v) => { i128::try_from(*v).map_err(|_| out_of_range(value))? }
+        // Map keys, when the value comes from JSON
+        Value::String(v) => { v.parse().map_err(|_| mismatch("integer", value))? }
+        v => { return Err(mismatch("integer", v)) }
+    };
+
+    T::try_from(v).map_err(|_| out_of_range(value))
+}
+
+fn out_of_range(value: &Value) -> SerbfError {
+    SerbfError::Schema(format!("Integer out of range: {:?}", value))
+}
+
+fn float(value: &Value) -> Result<f64, SerbfError> {
+    match value {
+        Value::F32(v) => { Ok(*v as f64) }
+        Value::F64(v) => { Ok(*v) }
+        Value::String(v) => { v.parse().map_err(|_| mismatch("float", value)) }
+        v => { int::<i128>(v).map(|v| v as f64).map_err(|_| mismatch("float", v)) }
+    }
+}
+
+/// The elements of a seq or tuple, bytes count as a seq of `u8`.
+fn elements(value: &Value) -> Result<Cow<'_, [Value]>, SerbfError> {
+    match value {
+        Value::Seq(v) => { Ok(Cow::Borrowed(v)) }
+        Value::Bytes(v) => { Ok(Cow::Owned(v.iter().map(|v| Value::U8(*v)).collect())) }
+        v => { Err(mismatch("seq", v)) }
+    }
+}
+
+fn find_variant<'a>(variants: &[Variant], value: &'a Value) -> Result<(u32, &'a Value), SerbfError> {
+    let (key, payload) = match value {
+        Value::Variant(index, v) => { return Ok((*index, v)) }
+        Value::Map(v) if v.len() == 1 => { (&v[0].0, &v[0].1) }
+        v => { (v, &Value::Unit) }
+    };
+
+    let by_name = match key {
+        Value::String(name) => { variants.iter().position(|v| v.name == *name) }
+        _ => { None }
+    };
+
+    match by_name {
+        Some(v) => { Ok((v as u32, payload)) }
+        None => { Ok((int(key).map_err(|_| mismatch("variant", value))?, payload)) }
+    }
+}
+
+impl Format {
+    fn merge(&mut self, other: Format) {
+        match (self, other) {
+            (_, Format::Unknown) => {}
+            (this @ Format::Unknown, other) => { *this = other }
+            (Format::Option(this), Format::Option(other)) => { this.merge(*other) }
+            (Format::Seq(this), Format::Seq(other)) => { this.merge(*other) }
+            (Format::Tuple(this), Format::Tuple(other)) => { merge_formats(this, other) }
+            (Format::Map { key, value }, Format::Map { key: other_key, value: other_value }) => {
+                key.merge(*other_key);
+                value.merge(*other_value);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Container {
+    fn merge(&mut self, other: Container) {
+        match (self, other) {
+            (Container::NewtypeStruct(this), Container::NewtypeStruct(other)) => { this.merge(other) }
+            (Container::TupleStruct(this), Container::TupleStruct(other)) => { merge_formats(this, other) }
+            (Container::Struct(this), Container::Struct(other)) => { merge_fields(this, other) }
+            (Container::Enum(this), Container::Enum(other)) => {
+                for (this, other) in this.iter_mut().zip(other) {
+                    this.format.merge(other.format);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl VariantFormat {
+    fn merge(&mut self, other: VariantFormat) {
+        match (self, other) {
+            (_, VariantFormat::Unknown) => {}
+            (this @ VariantFormat::Unknown, other) => { *this = other }
+            (VariantFormat::Newtype(this), VariantFormat::Newtype(other)) => { this.merge(other) }
+            (VariantFormat::Tuple(this), VariantFormat::Tuple(other)) => { merge_formats(this, other) }
+            (VariantFormat::Struct(this), VariantFormat::Struct(other)) => { merge_fields(this, other) }
+            _ => {}
+        }
+    }
+}
+
+fn merge_formats(this: &mut [Format], other: Vec<Format>) {
+    for (this, other) in this.iter_mut().zip(other) {
+        this.merge(other);
+    }
+}
+
+fn merge_fields(this: &mut [Field], other: Vec<Field>) {
+    for (this, other) in this.iter_mut().zip(other) {
+        this.format.merge(other.format);
+    }
+}
+
+fn to_fields(names: &'static [&'static str], formats: Vec<Format>) -> Vec<Field> {
+    names.iter()
+        .zip(formats)
+        .map(|(name, format)| Field { name: name.to_string(), format })
+        .collect()
+}
+
+#[derive(Default)]
+struct Tracer {
+    types: BTreeMap<String, Container>,
+
+    /// Variants of each enum that have been picked at least once
+    explored: BTreeMap<&'static str, Vec<bool>>,
+
+    /// Containers currently being traced, with the picked variant for enums
+    stack: Vec<(&'static str, Option<u32>)>,
+}
+
+impl Tracer {
+    fn explored(&self) -> usize {
+        self.explored.values().flatten().filter(|v| **v).count()
+    }
+
+    fn complete(&self) -> bool {
+        self.explored.values().flatten().all(|v| *v)
+    }
+
+    /// Whether some container is nested in itself, at which point optional data is left out.
+    fn in_recursion(&self) -> bool {
+        self.stack.iter().enumerate().any(|(i, (name, _))| self.stack[..i].iter().any(|(other, _)| other == name))
+    }
+
+    fn pick_variant(&mut self, name: &'static str, len: usize) -> u32 {
+        let explored = self.explored.entry(name).or_insert_with(|| vec![false; len]);
+
+        let active: Vec<u32> = self.stack.iter()
+            .filter(|(other, _)| *other == name)
+            .filter_map(|(_, variant)| *variant)
+            .collect();
+
+        // Inside itself, any variant that isn't already on the stack may end the recursion
+        let index = match active.is_empty() {
+            true => { explored.iter().position(|v| !*v).unwrap_or(0) as u32 }
+            false => { (0..len as u32).find(|v| !active.contains(v)).unwrap_or(0) }
+        };
+
+        if let Some(v) = explored.get_mut(index as usize) {
+            *v = true;
+        }
+
+        index
+    }
+
+    fn record(&mut self, name: &'static str, container: Container) {
+        match self.types.get_mut(name) {
+            Some(v) => { v.merge(container) }
+            None => { self.types.insert(name.to_string(), container); }
+        }
+    }
+}
+
+struct TraceDeserializer<'a> {
+    tracer: &'a mut Tracer,
+    format: &'a mut Format,
+}
+
+impl<'de> Deserializer<'de> for TraceDeserializer<'_> {
+    type Error = SerbfError;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        Err(SerbfError::Message("Untyped values can't be traced".to_string()))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Bool);
+
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::I8);
+
+        visitor.visit_i8(0)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::I16);
+
+        visitor.visit_i16(0)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::I32);
+
+        visitor.visit_i32(0)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::I64);
+
+        visitor.visit_i64(0)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::I128);
+
+        visitor.visit_i128(0)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::U8);
+
+        visitor.visit_u8(0)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::U16);
+
+        visitor.visit_u16(0)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::U32);
+
+        visitor.visit_u32(0)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::U64);
+
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::U128);
+
+        visitor.visit_u128(0)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::F32);
+
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::F64);
+
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Char);
+
+        visitor.visit_char('\0')
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Str);
+
+        visitor.visit_borrowed_str("")
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Bytes);
+
+        visitor.visit_borrowed_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let mut inner = Format::Unknown;
+
+        let value = match self.tracer.in_recursion() {
+            true => { visitor.visit_none::<SerbfError>()? }
+            false => { visitor.visit_some(TraceDeserializer { tracer: &mut *self.tracer, format: &mut inner })? }
+        };
+
+        self.format.merge(Format::Option(Box::new(inner)));
+
+        Ok(value)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Unit);
+
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Named(name.to_string()));
+        self.tracer.record(name, Container::UnitStruct);
+
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Named(name.to_string()));
+
+        let mut inner = Format::Unknown;
+
+        self.tracer.stack.push((name, None));
+        let value = visitor.visit_newtype_struct(TraceDeserializer { tracer: &mut *self.tracer, format: &mut inner })?;
+        self.tracer.stack.pop();
+
+        self.tracer.record(name, Container::NewtypeStruct(inner));
+
+        Ok(value)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let len = match self.tracer.in_recursion() {
+            true => { 0 }
+            false => { 1 }
+        };
+
+        let mut element = [Format::Unknown];
+
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut element[..len], index: 0 })?;
+
+        let [element] = element;
+        self.format.merge(Format::Seq(Box::new(element)));
+
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let mut formats = vec![Format::Unknown; len];
+
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut formats, index: 0 })?;
+
+        self.format.merge(Format::Tuple(formats));
+
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(self, name: &'static str, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Named(name.to_string()));
+
+        let mut formats = vec![Format::Unknown; len];
+
+        self.tracer.stack.push((name, None));
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut formats, index: 0 })?;
+        self.tracer.stack.pop();
+
+        self.tracer.record(name, Container::TupleStruct(formats));
+
+        Ok(value)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let mut key = Format::Unknown;
+        let mut value = Format::Unknown;
+
+        let res = visitor.visit_map(TraceMapAccess {
+            remaining: !self.tracer.in_recursion(),
+            tracer: &mut *self.tracer,
+            key: &mut key,
+            value: &mut value,
+        })?;
+
+        self.format.merge(Format::Map { key: Box::new(key), value: Box::new(value) });
+
+        Ok(res)
+    }
+
+    fn deserialize_struct<V>(self, name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Named(name.to_string()));
+
+        let mut formats = vec![Format::Unknown; fields.len()];
+
+        self.tracer.stack.push((name, None));
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut formats, index: 0 })?;
+        self.tracer.stack.pop();
+
+        self.tracer.record(name, Container::Struct(to_fields(fields, formats)));
+
+        Ok(value)
+    }
+
+    fn deserialize_enum<V>(self, name: &'static str, variants: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.format.merge(Format::Named(name.to_string()));
+
+        self.tracer.record(name, Container::Enum(variants.iter()
+            .map(|v| Variant { name: v.to_string(), format: VariantFormat::Unknown })
+            .collect()));
+
+        let index = self.tracer.pick_variant(name, variants.len());
+        let mut format = VariantFormat::Unknown;
+
+        self.tracer.stack.push((name, Some(index)));
+        let value = visitor.visit_enum(TraceEnumAccess { tracer: &mut *self.tracer, format: &mut format, index })?;
+        self.tracer.stack.pop();
+
+        if let Some(Container::Enum(v)) = self.tracer.types.get_mut(name) {
+            v[index as usize].format.merge(format);
+        }
+
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+struct TraceSeqAccess<'a> {
+    tracer: &'a mut Tracer,
+    formats: &'a mut [Format],
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for TraceSeqAccess<'_> {
+    type Error = SerbfError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>
+    {
+        let format = match self.formats.get_mut(self.index) {
+            Some(v) => { v }
+            None => { return Ok(None) }
+        };
+
+        self.index += 1;
+
+        seed.deserialize(TraceDeserializer { tracer: &mut *self.tracer, format }).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.formats.len() - self.index)
+    }
+}
+
+struct TraceMapAccess<'a> {
+    tracer: &'a mut Tracer,
+    key: &'a mut Format,
+    value: &'a mut Format,
+    remaining: bool,
+}
+
+impl<'de> MapAccess<'de> for TraceMapAccess<'_> {
+    type Error = SerbfError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>
+    {
+        if !self.remaining {
+            return Ok(None);
+        }
+
+        self.remaining = false;
+
+        seed.deserialize(TraceDeserializer { tracer: &mut *self.tracer, format: &mut *self.key }).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>
+    {
+        seed.deserialize(TraceDeserializer { tracer: &mut *self.tracer, format: &mut *self.value })
+    }
+}
+
+struct TraceEnumAccess<'a> {
+    tracer: &'a mut Tracer,
+    format: &'a mut VariantFormat,
+    index: u32,
+}
+
+impl<'de> EnumAccess<'de> for TraceEnumAccess<'_> {
+    type Error = SerbfError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>
+    {
+        let value = seed.deserialize(IntoDeserializer::<SerbfError>::into_deserializer(self.index))?;
+
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for TraceEnumAccess<'_> {
+    type Error = SerbfError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        self.format.merge(VariantFormat::Unit);
+
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>
+    {
+        let mut inner = Format::Unknown;
+
+        let value = seed.deserialize(TraceDeserializer { tracer: &mut *self.tracer, format: &mut inner })?;
+
+        self.format.merge(VariantFormat::Newtype(inner));
+
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let mut formats = vec![Format::Unknown; len];
+
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut formats, index: 0 })?;
+
+        self.format.merge(VariantFormat::Tuple(formats));
+
+        Ok(value)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>
+    {
+        let mut formats = vec![Format::Unknown; fields.len()];
+
+        let value = visitor.visit_seq(TraceSeqAccess { tracer: &mut *self.tracer, formats: &mut formats, index: 0 })?;
+
+        self.format.merge(VariantFormat::Struct(to_fields(fields, formats)));
+
+        Ok(value)
+    }
+}
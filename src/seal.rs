@@ -0,0 +1,157 @@
+use std::io::{Cursor, Error, ErrorKind};
+use std::sync::Arc;
+use aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::de::SerbfDeserializer;
+use crate::envelope::Envelope;
+use crate::error::SerbfError;
+use crate::ser::SerbfSerializer;
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cipher {
+    #[cfg(feature = "chacha20poly1305")]
+    ChaCha20Poly1305,
+
+    #[cfg(feature = "aes-gcm")]
+    Aes256Gcm,
+}
+
+impl Cipher {
+    fn id(&self) -> u8 {
+        match self {
+            #[cfg(feature = "chacha20poly1305")]
+            Cipher::ChaCha20Poly1305 => { 1 }
+            #[cfg(feature = "aes-gcm")]
+            Cipher::Aes256Gcm => { 2 }
+        }
+    }
+}
+
+/// Seals serialized payloads as `cipher id | nonce | ciphertext and tag` under a 256-bit key.
+///
+/// Associated data is authenticated but not stored, the same bytes have to be passed when opening.
+#[derive(Clone)]
+pub struct Sealer {
+    cipher: Cipher,
+    key: [u8; 32],
+}
+
+impl Sealer {
+    pub fn new(cipher: Cipher, key: [u8; 32]) -> Self {
+        Self {
+            cipher,
+            key,
+        }
+    }
+
+    pub fn seal(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, SerbfError> {
+        let payload = Payload { msg: plaintext, aad: associated_data };
+
+        let (nonce, ciphertext) = match self.cipher {
+            #[cfg(feature = "chacha20poly1305")]
+            Cipher::ChaCha20Poly1305 => {
+                let nonce = chacha20poly1305::ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                let cipher = chacha20poly1305::ChaCha20Poly1305::new(&self.key.into());
+
+                (nonce, cipher.encrypt(&nonce, payload))
+            }
+            #[cfg(feature = "aes-gcm")]
+            Cipher::Aes256Gcm => {
+                let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+                let cipher = aes_gcm::Aes256Gcm::new(&self.key.into());
+
+                (nonce, cipher.encrypt(&nonce, payload))
+            }
+        };
+
+        let ciphertext = match ciphertext {
+            Ok(v) => { v }
+            Err(_) => { return Err(SerbfError::Encryption) }
+        };
+
+        let mut buf = Vec::with_capacity(1 + NONCE_LEN + ciphertext.len());
+
+        buf.push(self.cipher.id());
+        buf.extend_from_slice(&nonce);
+        buf.extend_from_slice(&ciphertext);
+
+        Ok(buf)
+    }
+
+    pub fn open(&self, sealed: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, SerbfError> {
+        if sealed.len() < 1 + NONCE_LEN {
+            return Err(SerbfError::IOError(Arc::new(Error::from(ErrorKind::UnexpectedEof))));
+        }
+
+        if sealed[0] != self.cipher.id() {
+            return Err(SerbfError::UnsupportedCipher(sealed[0]));
+        }
+
+        let nonce = &sealed[1..1 + NONCE_LEN];
+        let payload = Payload { msg: &sealed[1 + NONCE_LEN..], aad: associated_data };
+
+        let plaintext = match self.cipher {
+            #[cfg(feature = "chacha20poly1305")]
+            Cipher::ChaCha20Poly1305 => {
+                chacha20poly1305::ChaCha20Poly1305::new(&self.key.into()).decrypt(nonce.into(), payload)
+            }
+            #[cfg(feature = "aes-gcm")]
+            Cipher::Aes256Gcm => {
+                aes_gcm::Aes256Gcm::new(&self.key.into()).decrypt(nonce.into(), payload)
+            }
+        };
+
+        match plaintext {
+            Ok(v) => { Ok(v) }
+            Err(_) => { Err(SerbfError::AuthenticationFailed) }
+        }
+    }
+
+    /// Serializes `value` behind a plaintext envelope header, which is authenticated as associated data.
+    pub fn seal_value<T>(&self, envelope: &Envelope, value: &T) -> Result<Vec<u8>, SerbfError>
+    where
+        T: ?Sized + Serialize
+    {
+        let mut header = Vec::new();
+        envelope.write(&mut header)?;
+
+        let mut serializer = SerbfSerializer::with_config(envelope.config);
+        value.serialize(&mut serializer)?;
+
+        let sealed = self.seal(&serializer.into_inner(), &header)?;
+
+        header.extend_from_slice(&sealed);
+
+        Ok(header)
+    }
+
+    /// Opens data written by `seal_value` and deserializes it with the envelope's config.
+    pub fn open_value<T>(&self, data: &[u8]) -> Result<(T, Envelope), SerbfError>
+    where
+        T: DeserializeOwned
+    {
+        let mut header = Cursor::new(data);
+
+        let envelope = Envelope::read(&mut header)?;
+
+        let (header, sealed) = data.split_at(header.position() as usize);
+
+        let plaintext = self.open(sealed, header)?;
+
+        let mut deserializer = SerbfDeserializer::with_config(&plaintext, envelope.config)?;
+
+        Ok((T::deserialize(&mut deserializer)?, envelope))
+    }
+}
+
+/// Generates a random key with the operating system's RNG.
+pub fn generate_key() -> [u8; 32] {
+    let mut key = [0; 32];
+
+    aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut key);
+
+    key
+}
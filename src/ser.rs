@@ -1,29 +1,314 @@
+use std::collections::HashMap;
 use std::io::Write;
 use std::sync::Arc;
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant};
 use serde::{Serialize, Serializer};
 use varint_rs::VarintWriter;
+use crate::checksum::ChecksumWriter;
+use crate::columnar;
+use crate::config::{BitPacking, CharEncoding, Compat, Endian, IntEncoding, SerbfConfig, StructEncoding, CANONICAL_NAN_F32, CANONICAL_NAN_F64};
+use crate::envelope::Envelope;
 use crate::error::SerbfError;
+use crate::tag::Tag;
 
-#[derive(Clone)]
-pub struct SerbfSerializer {
+/// Streams the encoding into `W`. Only length-prefixed structs, canonical maps and bit-packed
+/// bytes are buffered, until their length, entry order or last bit is known.
+#[derive(Clone, Default)]
+pub struct SerbfSerializer<W = Vec<u8>> {
+    writer: ChecksumWriter<W>,
+    config: SerbfConfig,
+
+    /// Output that can't be written yet
     buf: Vec<u8>,
+
+    /// Where the bodies of the length-prefixed structs currently being written start
+    struct_starts: Vec<usize>,
+
+    /// Key and value offsets of the entries of the canonical maps currently being written
+    map_entries: Vec<Vec<(usize, usize)>>,
+
+    /// Table index of every string written so far, when interning
+    strings: HashMap<String, usize>,
+
+    /// Position in `buf` of the bit-packed byte being filled and how many of its bits are used
+    bits: Option<(usize, u8)>,
+
+    /// Bit-packed bytes of the structs around the length-prefixed ones being written
+    outer_bits: Vec<Option<(usize, u8)>>,
 }
 
 impl SerbfSerializer {
     pub fn new() -> Self {
+        Self::with_config(SerbfConfig::default())
+    }
+
+    pub fn with_config(config: SerbfConfig) -> Self {
+        Self::from_writer(Vec::new(), config)
+    }
+
+    /// Starts the output with the envelope header and encodes with the envelope's config.
+    pub fn with_envelope(envelope: &Envelope) -> Result<Self, SerbfError> {
+        Self::from_writer_with_envelope(Vec::new(), envelope)
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        // Writing into a Vec can't fail
+        self.finish().unwrap_or_default()
+    }
+}
+
+impl<W: Write> SerbfSerializer<W> {
+    pub fn from_writer(writer: W, config: SerbfConfig) -> Self {
         Self {
+            writer: config.checksum.writer(writer),
+            config,
             buf: Vec::new(),
+            struct_starts: Vec::new(),
+            map_entries: Vec::new(),
+            strings: HashMap::new(),
+            bits: None,
+            outer_bits: Vec::new(),
         }
     }
 
-    pub fn into_inner(self) -> Vec<u8> {
-        self.buf
+    pub fn from_writer_with_envelope(writer: W, envelope: &Envelope) -> Result<Self, SerbfError> {
+        let mut serializer = Self::from_writer(writer, envelope.config);
+
+        envelope.write(&mut serializer.writer)?;
+
+        Ok(serializer)
+    }
+
+    pub fn config(&self) -> &SerbfConfig {
+        &self.config
+    }
+
+    /// Writes the checksum trailer, if any, and returns the writer.
+    pub fn finish(mut self) -> Result<W, SerbfError> {
+        self.bits = None;
+        self.flush_buf()?;
+
+        match self.writer.finish() {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    fn buffering(&self) -> bool {
+        !self.struct_starts.is_empty() || !self.map_entries.is_empty() || self.bits.is_some()
+    }
+
+    fn output(&mut self) -> &mut dyn Write {
+        match self.buffering() {
+            true => { &mut self.buf }
+            false => { &mut self.writer }
+        }
+    }
+
+    /// Hands the buffered output to the writer once nothing in it can change anymore.
+    fn flush_buf(&mut self) -> Result<(), SerbfError> {
+        if self.buffering() || self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let res = self.writer.write_all(&self.buf);
+
+        self.buf.clear();
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    fn write_tag(&mut self, tag: Tag) -> Result<(), SerbfError> {
+        if !self.config.self_describing {
+            return Ok(());
+        }
+
+        match self.output().write_u8(tag as u8) {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    /// Adds the low `count` bits of `value` to the current bit-packed byte, starting a new one
+    /// when it's full.
+    fn write_bits(&mut self, value: u32, count: u8) -> Result<(), SerbfError> {
+        for i in 0..count {
+            let (pos, used) = match self.bits {
+                Some(v) => { v }
+                None => { self.buf.push(0); (self.buf.len() - 1, 0) }
+            };
+
+            self.buf[pos] |= ((value >> i & 1) as u8) << used;
+
+            match used + 1 {
+                8 => { self.bits = None; self.flush_buf()?; }
+                used => { self.bits = Some((pos, used)); }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the current bit-packed byte when every struct gets its own.
+    fn end_struct_bits(&mut self) -> Result<(), SerbfError> {
+        if self.config.bit_packing == BitPacking::PerStruct {
+            self.bits = None;
+        }
+
+        self.flush_buf()
+    }
+
+    fn length_prefixed_structs(&self) -> bool {
+        !self.config.self_describing && self.config.struct_encoding == StructEncoding::LengthPrefixed
+    }
+
+    fn end_struct(&mut self) -> Result<(), SerbfError> {
+        let start = match self.struct_starts.pop() {
+            Some(v) => { v }
+            None => { return self.end_struct_bits() }
+        };
+
+        let mut len = (self.buf.len() - start) as u64;
+
+        // The length also tells how many bits of the body's last packed byte are padding, so
+        // fields added later aren't read from them
+        if self.config.packs_bits() {
+            let padding = match self.bits {
+                Some((_, used)) => { 8 - used }
+                None => { 0 }
+            };
+
+            len = len << 3 | padding as u64;
+        }
+
+        self.bits = self.outer_bits.pop().flatten();
+
+        let mut prefix = Vec::new();
+
+        match prefix.write_u64_varint(len) {
+            Ok(_) => {}
+            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        }
+
+        self.buf.splice(start..start, prefix);
+
+        self.end_struct_bits()
+    }
+
+    /// Writes the length of a string, byte string, sequence or map as a u64, so it decodes the
+    /// same on targets with a narrower `usize`.
+    fn write_len(&mut self, len: usize) -> Result<(), SerbfError> {
+        let res = match (self.config.compat, self.config.endian) {
+            (Compat::Off | Compat::Postcard, _) => { self.output().write_u64_varint(len as u64) }
+            (Compat::Bincode, Endian::Little) => { self.output().write_u64::<LittleEndian>(len as u64) }
+            (Compat::Bincode, Endian::Big) => { self.output().write_u64::<BigEndian>(len as u64) }
+        };
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
+    }
+
+    fn write_tuple_header(&mut self, len: usize) -> Result<(), SerbfError> {
+        self.write_tag(Tag::Seq)?;
+
+        match self.config.tuple_lengths() {
+            true => { self.write_len(len) }
+            false => { Ok(()) }
+        }
+    }
+
+    fn write_map_header(&mut self, len: usize) -> Result<(), SerbfError> {
+        self.write_tag(Tag::Map)?;
+
+        self.write_len(len)
+    }
+
+    /// Reorders the entries of a canonical map by their encoded key bytes.
+    fn end_map(&mut self) -> Result<(), SerbfError> {
+        let entries = match self.map_entries.pop() {
+            Some(v) => { v }
+            None => { return Ok(()) }
+        };
+
+        let start = match entries.first() {
+            Some(v) => { v.0 }
+            None => { return self.flush_buf() }
+        };
+
+        let body = self.buf.split_off(start);
+
+        let mut sorted = entries.iter().enumerate().map(|(i, (key, value))| {
+            let end = match entries.get(i + 1) {
+                Some(v) => { v.0 }
+                None => { start + body.len() }
+            };
+
+            (&body[key - start..value - start], &body[key - start..end - start])
+        }).collect::<Vec<_>>();
+
+        sorted.sort_by_key(|(key, _)| *key);
+
+        for (_, entry) in sorted {
+            self.buf.extend_from_slice(entry);
+        }
+
+        self.flush_buf()
+    }
+
+    /// Marks where the key of the next entry of the current canonical map starts.
+    pub(crate) fn start_map_key(&mut self) {
+        let start = self.buf.len();
+
+        if let Some(entries) = self.map_entries.last_mut() {
+            entries.push((start, start));
+        }
+    }
+
+    pub(crate) fn start_map_value(&mut self) {
+        let start = self.buf.len();
+
+        if let Some(entry) = self.map_entries.last_mut().and_then(|v| v.last_mut()) {
+            entry.1 = start;
+        }
+    }
+
+    /// Writes what precedes the value of a `Some`.
+    pub(crate) fn write_some(&mut self) -> Result<(), SerbfError> {
+        match self.config.self_describing {
+            true => { self.write_tag(Tag::Some) }
+            false => { self.serialize_bool(true) }
+        }
+    }
+
+    pub(crate) fn write_variant_index(&mut self, variant_index: u32) -> Result<(), SerbfError> {
+        self.write_tag(Tag::Variant)?;
+
+        let variant_index = match self.config.packs_bits() {
+            true if variant_index < 7 => { return self.write_bits(variant_index, 3) }
+            true => { self.write_bits(7, 3)?; variant_index - 7 }
+            false => { variant_index }
+        };
+
+        let res = match (self.config.compat, self.config.endian) {
+            (Compat::Off | Compat::Postcard, _) => { self.output().write_u32_varint(variant_index) }
+            (Compat::Bincode, Endian::Little) => { self.output().write_u32::<LittleEndian>(variant_index) }
+            (Compat::Bincode, Endian::Big) => { self.output().write_u32::<BigEndian>(variant_index) }
+        };
+
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
     }
 }
 
-impl<'a> Serializer for &'a mut SerbfSerializer {
+impl<W: Write> Serializer for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
     type SerializeSeq = Self;
@@ -34,10 +319,16 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
 
-    fn serialize_bool(mut self, v: bool) -> Result<Self::Ok, Self::Error> {
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::Bool)?;
+
+        if self.config.packs_bits() {
+            return self.write_bits(v as u32, 1);
+        }
+
         let res = match v {
-            true => { self.buf.write_u8(1) }
-            false => { self.buf.write_u8(0) }
+            true => { self.output().write_u8(1) }
+            false => { self.output().write_u8(0) }
         };
 
         match res {
@@ -46,113 +337,232 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
         }
     }
 
-    fn serialize_i8(mut self, v: i8) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_i8(v) {
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::I8)?;
+
+        match self.output().write_i8(v) {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_i16(mut self, v: i16) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_i16::<LittleEndian>(v) {
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::I16)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_i16_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_i16::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_i16::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_i32(mut self, v: i32) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_i32::<LittleEndian>(v) {
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::I32)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_i32_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_i32::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_i32::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_i64(mut self, v: i64) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_i64::<LittleEndian>(v) {
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::I64)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_i64_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_i64::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_i64::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_i128(mut self, v: i128) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_i128::<LittleEndian>(v) {
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::I128)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_i128_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_i128::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_i128::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_u8(mut self, v: u8) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u8(v) {
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::U8)?;
+
+        match self.output().write_u8(v) {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_u16(mut self, v: u16) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u16::<LittleEndian>(v) {
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::U16)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_u16_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_u16::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_u16::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_u32(mut self, v: u32) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u32::<LittleEndian>(v) {
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::U32)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_u32_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_u32::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_u32::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_u64(mut self, v: u64) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u64::<LittleEndian>(v) {
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::U64)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_u64_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_u64::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_u64::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_u128(mut self, v: u128) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u128::<LittleEndian>(v) {
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::U128)?;
+
+        let res = match (self.config.int_encoding, self.config.endian) {
+            (IntEncoding::Varint, _) => { self.output().write_u128_varint(v) }
+            (IntEncoding::Fixed, Endian::Little) => { self.output().write_u128::<LittleEndian>(v) }
+            (IntEncoding::Fixed, Endian::Big) => { self.output().write_u128::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_f32(mut self, v: f32) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_f32::<LittleEndian>(v) {
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::F32)?;
+
+        let v = match self.config.canonical && v.is_nan() {
+            true => { f32::from_bits(CANONICAL_NAN_F32) }
+            false => { v }
+        };
+
+        let res = match self.config.endian {
+            Endian::Little => { self.output().write_f32::<LittleEndian>(v) }
+            Endian::Big => { self.output().write_f32::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
-    fn serialize_f64(mut self, v: f64) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_f64::<LittleEndian>(v) {
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::F64)?;
+
+        let v = match self.config.canonical && v.is_nan() {
+            true => { f64::from_bits(CANONICAL_NAN_F64) }
+            false => { v }
+        };
+
+        let res = match self.config.endian {
+            Endian::Little => { self.output().write_f64::<LittleEndian>(v) }
+            Endian::Big => { self.output().write_f64::<BigEndian>(v) }
+        };
+
+        match res {
             Ok(_) => { Ok(()) }
             Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
         }
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        let v = v.to_string();
-
-        match self.buf.write_usize_varint(v.len()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        self.write_tag(Tag::Char)?;
+
+        let res = match (self.config.char_encoding(), self.config.int_encoding, self.config.endian) {
+            (CharEncoding::Scalar, IntEncoding::Varint, _) => { self.output().write_u32_varint(v as u32) }
+            (CharEncoding::Scalar, IntEncoding::Fixed, Endian::Little) => { self.output().write_u32::<LittleEndian>(v as u32) }
+            (CharEncoding::Scalar, IntEncoding::Fixed, Endian::Big) => { self.output().write_u32::<BigEndian>(v as u32) }
+            (encoding, _, _) => {
+                let mut buf = [0; 4];
+                let v = v.encode_utf8(&mut buf);
+
+                if encoding == CharEncoding::Str {
+                    self.write_len(v.len())?;
+                }
+
+                self.output().write_all(v.as_bytes())
+            }
         };
 
-        match self.buf.write_all(v.to_string().as_bytes()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
-
-        Ok(())
+        match res {
+            Ok(_) => { Ok(()) }
+            Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+        }
     }
 
-    fn serialize_str(mut self, v: &str) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_usize_varint(v.len()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::Str)?;
+
+        // Interned strings start with a number whose low bit tells a back-reference from a length
+        if self.config.interns_strings() {
+            if let Some(index) = self.strings.get(v) {
+                let index = (*index << 1) | 1;
+
+                return self.write_len(index);
+            }
 
-        match self.buf.write_all(v.as_bytes()) {
+            let index = self.strings.len();
+            self.strings.insert(v.to_string(), index);
+
+            self.write_len(v.len() << 1)?;
+
+            return match self.output().write_all(v.as_bytes()) {
+                Ok(_) => { Ok(()) }
+                Err(e) => { Err(SerbfError::IOError(Arc::new(e))) }
+            };
+        }
+
+        self.write_len(v.len())?;
+
+        match self.output().write_all(v.as_bytes()) {
             Ok(_) => {}
             Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
         };
@@ -160,13 +570,12 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
         Ok(())
     }
 
-    fn serialize_bytes(mut self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_usize_varint(v.len()) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        };
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        self.write_tag(Tag::Bytes)?;
 
-        match self.buf.write_all(v) {
+        self.write_len(v.len())?;
+
+        match self.output().write_all(v) {
             Ok(_) => {}
             Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
         };
@@ -174,51 +583,55 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
         Ok(())
     }
 
-    fn serialize_none(mut self) -> Result<Self::Ok, Self::Error> {
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        if self.config.self_describing {
+            return self.write_tag(Tag::None);
+        }
+
         self.serialize_bool(false)
     }
 
-    fn serialize_some<T>(mut self, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize
     {
-        self.serialize_bool(true)?;
+        self.write_some()?;
 
         value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        // ¯\_(:/)_/¯ nothing to do
+        // ¯\_(:/)_/¯ nothing to do, unless the unit has to be tagged
 
-        Ok(())
+        self.write_tag(Tag::Unit)
     }
 
-    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
         self.serialize_unit()
     }
 
-    fn serialize_unit_variant(mut self, name: &'static str, variant_index: u32, variant: &'static str) -> Result<Self::Ok, Self::Error> {
-        match self.buf.write_u32_varint(variant_index) {
-            Ok(_) => { Ok(()) }
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.write_variant_index(variant_index)?;
+
+        self.write_tag(Tag::Unit)
     }
 
     fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize
     {
+        if name == columnar::TOKEN {
+            return columnar::with_config(self.config, || value.serialize(self));
+        }
+
         value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T>(mut self, name: &'static str, variant_index: u32, variant: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_newtype_variant<T>(self, _name: &'static str, variant_index: u32, _variant: &'static str, value: &T) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize
     {
-        match self.buf.write_u32_varint(variant_index) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+        self.write_variant_index(variant_index)?;
 
         value.serialize(self)
     }
@@ -229,37 +642,27 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
             Some(v) => { v }
         };
 
-        match self.buf.write_usize_varint(len) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+        self.write_tag(Tag::Seq)?;
+        self.write_len(len)?;
 
         Ok(self)
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        match self.buf.write_usize_varint(len) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+        self.write_tuple_header(len)?;
 
         Ok(self)
     }
 
-    fn serialize_tuple_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        match self.buf.write_usize_varint(len) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.write_tuple_header(len)?;
 
         Ok(self)
     }
 
-    fn serialize_tuple_variant(self, name: &'static str, variant_index: u32, variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        match self.buf.write_usize_varint(len) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
-        }
+    fn serialize_tuple_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str, len: usize) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        self.write_variant_index(variant_index)?;
+        self.write_tuple_header(len)?;
 
         Ok(self)
     }
@@ -270,20 +673,38 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
             Some(v) => { v }
         };
 
-        match self.buf.write_usize_varint(len) {
-            Ok(_) => {}
-            Err(e) => { return Err(SerbfError::IOError(Arc::new(e))) }
+        self.write_map_header(len)?;
+
+        if self.config.canonical {
+            self.map_entries.push(Vec::with_capacity(len));
         }
 
         Ok(self)
     }
 
-    fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+        // Structs are written as maps keyed by field name when self-describing
+        if self.config.self_describing {
+            self.write_map_header(len)?;
+
+            return Ok(self);
+        }
+
+        self.end_struct_bits()?;
+
+        // The body of a length-prefixed struct packs its bits into bytes of its own
+        if self.length_prefixed_structs() {
+            self.struct_starts.push(self.buf.len());
+            self.outer_bits.push(self.bits.take());
+        }
+
         Ok(self)
     }
 
-    fn serialize_struct_variant(self, name: &'static str, variant_index: u32, variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Ok(self)
+    fn serialize_struct_variant(self, name: &'static str, variant_index: u32, _variant: &'static str, len: usize) -> Result<Self::SerializeStructVariant, Self::Error> {
+        self.write_variant_index(variant_index)?;
+
+        self.serialize_struct(name, len)
     }
 
     fn is_human_readable(&self) -> bool {
@@ -291,7 +712,7 @@ impl<'a> Serializer for &'a mut SerbfSerializer {
     }
 }
 
-impl<'a> SerializeSeq for &'a mut SerbfSerializer {
+impl<W: Write> SerializeSeq for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -307,7 +728,7 @@ impl<'a> SerializeSeq for &'a mut SerbfSerializer {
     }
 }
 
-impl<'a> SerializeTuple for &'a mut SerbfSerializer {
+impl<W: Write> SerializeTuple for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -323,7 +744,7 @@ impl<'a> SerializeTuple for &'a mut SerbfSerializer {
     }
 }
 
-impl<'a> SerializeTupleStruct for &'a mut SerbfSerializer {
+impl<W: Write> SerializeTupleStruct for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -339,7 +760,7 @@ impl<'a> SerializeTupleStruct for &'a mut SerbfSerializer {
     }
 }
 
-impl<'a> SerializeTupleVariant for &'a mut SerbfSerializer {
+impl<W: Write> SerializeTupleVariant for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -355,7 +776,7 @@ impl<'a> SerializeTupleVariant for &'a mut SerbfSerializer {
     }
 }
 
-impl<'a> SerializeMap for &'a mut SerbfSerializer {
+impl<W: Write> SerializeMap for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -363,6 +784,8 @@ impl<'a> SerializeMap for &'a mut SerbfSerializer {
     where
         T: ?Sized + Serialize
     {
+        self.start_map_key();
+
         key.serialize(&mut **self)
     }
 
@@ -370,15 +793,17 @@ impl<'a> SerializeMap for &'a mut SerbfSerializer {
     where
         T: ?Sized + Serialize
     {
+        self.start_map_value();
+
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.end_map()
     }
 }
 
-impl<'a> SerializeStruct for &'a mut SerbfSerializer {
+impl<W: Write> SerializeStruct for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -386,15 +811,19 @@ impl<'a> SerializeStruct for &'a mut SerbfSerializer {
     where
         T: ?Sized + Serialize
     {
+        if self.config.self_describing {
+            (&mut **self).serialize_str(key)?;
+        }
+
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.end_struct()
     }
 }
 
-impl<'a> SerializeStructVariant for &'a mut SerbfSerializer {
+impl<W: Write> SerializeStructVariant for &mut SerbfSerializer<W> {
     type Ok = ();
     type Error = SerbfError;
 
@@ -402,10 +831,10 @@ impl<'a> SerializeStructVariant for &'a mut SerbfSerializer {
     where
         T: ?Sized + Serialize
     {
-        value.serialize(&mut **self)
+        SerializeStruct::serialize_field(self, key, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(())
+        self.end_struct()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,62 @@
+use crate::error::SerbfError;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Tag {
+    Unit = 0,
+    Bool = 1,
+    I8 = 2,
+    I16 = 3,
+    I32 = 4,
+    I64 = 5,
+    I128 = 6,
+    U8 = 7,
+    U16 = 8,
+    U32 = 9,
+    U64 = 10,
+    U128 = 11,
+    F32 = 12,
+    F64 = 13,
+    Char = 14,
+    Str = 15,
+    Bytes = 16,
+    None = 17,
+    Some = 18,
+    Seq = 19,
+    Map = 20,
+    Variant = 21,
+}
+
+impl TryFrom<u8> for Tag {
+    type Error = SerbfError;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        let tag = match v {
+            0 => { Tag::Unit }
+            1 => { Tag::Bool }
+            2 => { Tag::I8 }
+            3 => { Tag::I16 }
+            4 => { Tag::I32 }
+            5 => { Tag::I64 }
+            6 => { Tag::I128 }
+            7 => { Tag::U8 }
+            8 => { Tag::U16 }
+            9 => { Tag::U32 }
+            10 => { Tag::U64 }
+            11 => { Tag::U128 }
+            12 => { Tag::F32 }
+            13 => { Tag::F64 }
+            14 => { Tag::Char }
+            15 => { Tag::Str }
+            16 => { Tag::Bytes }
+            17 => { Tag::None }
+            18 => { Tag::Some }
+            19 => { Tag::Seq }
+            20 => { Tag::Map }
+            21 => { Tag::Variant }
+            v => { return Err(SerbfError::InvalidTag(v)) }
+        };
+
+        Ok(tag)
+    }
+}
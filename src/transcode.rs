@@ -0,0 +1,315 @@
+use std::cell::RefCell;
+use std::io::Write;
+use serde::ser::{Error, SerializeMap, SerializeSeq, SerializeTuple};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::de::SerbfDeserializer;
+use crate::error::SerbfError;
+use crate::schema::{Container, Field, Format, Schema, VariantFormat};
+use crate::ser::SerbfSerializer;
+use crate::tag::Tag;
+use crate::value::Value;
+
+/// Streams self-describing data into any `Serializer`, e.g. to turn it into JSON without its
+/// Rust type. Enums carry only their index, so like `Value` they become a map from the index to
+/// the payload in human-readable formats.
+pub fn transcode<S>(deserializer: &mut SerbfDeserializer, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer
+{
+    if !deserializer.config().self_describing {
+        return Err(S::Error::custom(SerbfError::AnyNotSupported));
+    }
+
+    let de = RefCell::new(deserializer);
+
+    Transcoder { schema: None, node: Node::Tagged, de: &de }.node(serializer)
+}
+
+/// Streams data described by `schema` into any `Serializer`. Structs become maps keyed by field
+/// name and enums are tagged with the variant name the way serde does by default, so the output
+/// can be read back with `transcode_from`. Self-describing data is transcoded from its tags
+/// alone, like with `transcode`, so its enums become maps from the variant index to the payload
+/// rather than being tagged with the variant name.
+pub fn transcode_with_schema<S>(deserializer: &mut SerbfDeserializer, schema: &Schema, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer
+{
+    let node = match deserializer.config().self_describing {
+        true => { Node::Tagged }
+        false => { Node::Format(&schema.root) }
+    };
+
+    let de = RefCell::new(deserializer);
+
+    Transcoder { schema: Some(schema), node, de: &de }.node(serializer)
+}
+
+/// Encodes the output of any self-describing `Deserializer`, like JSON, as described by
+/// `schema`, see `Schema::encode`. Without a schema values are written with the types the
+/// deserializer reports. Only one value is read, checking that nothing follows it is up to the
+/// caller, e.g. with `serde_json::Deserializer::end`.
+pub fn transcode_from<'de, D, W>(deserializer: D, schema: Option<&Schema>, serializer: &mut SerbfSerializer<W>) -> Result<(), SerbfError>
+where
+    D: Deserializer<'de>,
+    W: Write
+{
+    let value = match Value::deserialize(deserializer) {
+        Ok(v) => { v }
+        Err(e) => { return Err(SerbfError::Message(e.to_string())) }
+    };
+
+    match schema {
+        Some(v) => { v.encode(&value, serializer) }
+        None => { value.serialize(serializer) }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Node<'s> {
+    Tagged,
+    Format(&'s Format),
+    Tuple(&'s [Format]),
+    Fields(&'s [Field]),
+}
+
+/// Reads the next value of `node` from the deserializer while it's being serialized.
+struct Transcoder<'s, 'a, 'b, 'de> {
+    schema: Option<&'s Schema>,
+    node: Node<'s>,
+    de: &'a RefCell<&'b mut SerbfDeserializer<'de>>,
+}
+
+impl<'s, 'de> Transcoder<'s, '_, '_, 'de> {
+    fn child(&self, node: Node<'s>) -> Self {
+        Self {
+            schema: self.schema,
+            node,
+            de: self.de,
+        }
+    }
+
+    fn read<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        E: Error,
+        F: FnOnce(&mut SerbfDeserializer<'de>) -> Result<T, SerbfError>
+    {
+        match f(&mut self.de.borrow_mut()) {
+            Ok(v) => { Ok(v) }
+            Err(e) => { Err(E::custom(e)) }
+        }
+    }
+
+    fn node<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.node {
+            Node::Tagged => { self.tagged(serializer) }
+            Node::Format(v) => { self.format(v, serializer) }
+            Node::Tuple(v) => { self.tuple(v, serializer) }
+            Node::Fields(v) => { self.fields(v, serializer) }
+        }
+    }
+
+    fn tagged<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.read(|d| d.read_tag())? {
+            Tag::Unit => { serializer.serialize_unit() }
+            Tag::Bool => { serializer.serialize_bool(self.read(|d| d.read_bool())?) }
+            Tag::I8 => { serializer.serialize_i8(self.read(|d| d.read_i8())?) }
+            Tag::I16 => { serializer.serialize_i16(self.read(|d| d.read_i16())?) }
+            Tag::I32 => { serializer.serialize_i32(self.read(|d| d.read_i32())?) }
+            Tag::I64 => { serializer.serialize_i64(self.read(|d| d.read_i64())?) }
+            Tag::I128 => { serializer.serialize_i128(self.read(|d| d.read_i128())?) }
+            Tag::U8 => { serializer.serialize_u8(self.read(|d| d.read_u8())?) }
+            Tag::U16 => { serializer.serialize_u16(self.read(|d| d.read_u16())?) }
+            Tag::U32 => { serializer.serialize_u32(self.read(|d| d.read_u32())?) }
+            Tag::U64 => { serializer.serialize_u64(self.read(|d| d.read_u64())?) }
+            Tag::U128 => { serializer.serialize_u128(self.read(|d| d.read_u128())?) }
+            Tag::F32 => { serializer.serialize_f32(self.read(|d| d.read_f32())?) }
+            Tag::F64 => { serializer.serialize_f64(self.read(|d| d.read_f64())?) }
+            Tag::Char => { serializer.serialize_char(self.read(|d| d.read_char())?) }
+            Tag::Str => { serializer.serialize_str(self.read(|d| d.read_str())?) }
+            Tag::Bytes => { serializer.serialize_bytes(self.read(|d| { let len = d.read_len()?; d.read_slice(len) })?) }
+            Tag::None => { serializer.serialize_none() }
+            Tag::Some => { serializer.serialize_some(&self.child(Node::Tagged)) }
+            Tag::Seq => {
+                let len = self.read(|d| d.read_len())?;
+
+                let mut seq = serializer.serialize_seq(Some(len))?;
+
+                for _ in 0..len {
+                    seq.serialize_element(&self.child(Node::Tagged))?;
+                }
+
+                seq.end()
+            }
+            Tag::Map => {
+                let len = self.read(|d| d.read_len())?;
+
+                let mut map = serializer.serialize_map(Some(len))?;
+
+                for _ in 0..len {
+                    map.serialize_key(&self.child(Node::Tagged))?;
+                    map.serialize_value(&self.child(Node::Tagged))?;
+                }
+
+                map.end()
+            }
+            Tag::Variant => {
+                let index = self.read(|d| d.read_variant_index())?;
+
+                if !serializer.is_human_readable() {
+                    return serializer.serialize_newtype_variant("Value", index, "", &self.child(Node::Tagged));
+                }
+
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(&index, &self.child(Node::Tagged))?;
+                map.end()
+            }
+        }
+    }
+
+    fn format<S: Serializer>(&self, format: &'s Format, serializer: S) -> Result<S::Ok, S::Error> {
+        match format {
+            Format::Unknown => { Err(S::Error::custom("Can't decode a value of unknown format")) }
+            Format::Unit => { serializer.serialize_unit() }
+            Format::Bool => { serializer.serialize_bool(self.read(|d| d.read_bool())?) }
+            Format::I8 => { serializer.serialize_i8(self.read(|d| d.read_i8())?) }
+            Format::I16 => { serializer.serialize_i16(self.read(|d| d.read_i16())?) }
+            Format::I32 => { serializer.serialize_i32(self.read(|d| d.read_i32())?) }
+            Format::I64 => { serializer.serialize_i64(self.read(|d| d.read_i64())?) }
+            Format::I128 => { serializer.serialize_i128(self.read(|d| d.read_i128())?) }
+            Format::U8 => { serializer.serialize_u8(self.read(|d| d.read_u8())?) }
+            Format::U16 => { serializer.serialize_u16(self.read(|d| d.read_u16())?) }
+            Format::U32 => { serializer.serialize_u32(self.read(|d| d.read_u32())?) }
+            Format::U64 => { serializer.serialize_u64(self.read(|d| d.read_u64())?) }
+            Format::U128 => { serializer.serialize_u128(self.read(|d| d.read_u128())?) }
+            Format::F32 => { serializer.serialize_f32(self.read(|d| d.read_f32())?) }
+            Format::F64 => { serializer.serialize_f64(self.read(|d| d.read_f64())?) }
+            Format::Char => { serializer.serialize_char(self.read(|d| d.read_char())?) }
+            Format::Str => { serializer.serialize_str(self.read(|d| d.read_str())?) }
+            Format::Bytes => { serializer.serialize_bytes(self.read(|d| { let len = d.read_len()?; d.read_slice(len) })?) }
+            Format::Option(v) => {
+                match self.read(|d| d.read_option_tag())? {
+                    false => { serializer.serialize_none() }
+                    true => { serializer.serialize_some(&self.child(Node::Format(v))) }
+                }
+            }
+            Format::Seq(v) => {
+                let len = self.read(|d| d.read_len())?;
+
+                let mut seq = serializer.serialize_seq(Some(len))?;
+
+                for _ in 0..len {
+                    seq.serialize_element(&self.child(Node::Format(v)))?;
+                }
+
+                seq.end()
+            }
+            Format::Tuple(v) => { self.tuple(v, serializer) }
+            Format::Map { key, value } => {
+                let len = self.read(|d| d.read_len())?;
+
+                let mut map = serializer.serialize_map(Some(len))?;
+
+                for _ in 0..len {
+                    map.serialize_key(&self.child(Node::Format(key)))?;
+                    map.serialize_value(&self.child(Node::Format(value)))?;
+                }
+
+                map.end()
+            }
+            Format::Named(name) => {
+                let container = match self.schema.and_then(|v| v.container(name)) {
+                    Some(v) => { v }
+                    None => { return Err(S::Error::custom(format!("Missing definition of {}", name))) }
+                };
+
+                match container {
+                    Container::UnitStruct => { serializer.serialize_unit() }
+                    Container::NewtypeStruct(v) => {
+                        self.read(|d| d.enter())?;
+                        let res = self.format(v, serializer);
+                        self.de.borrow_mut().leave();
+
+                        res
+                    }
+                    Container::TupleStruct(v) => { self.tuple(v, serializer) }
+                    Container::Struct(v) => { self.fields(v, serializer) }
+                    Container::Enum(v) => {
+                        let index = self.read(|d| d.read_variant_index())?;
+
+                        let variant = match v.get(index as usize) {
+                            Some(v) => { v }
+                            None => { return Err(S::Error::custom(SerbfError::InvalidEnumID)) }
+                        };
+
+                        let payload = match &variant.format {
+                            VariantFormat::Unknown => { return Err(S::Error::custom(format!("Unknown format of variant {}::{}", name, variant.name))) }
+                            VariantFormat::Unit => { return serializer.serialize_str(&variant.name) }
+                            VariantFormat::Newtype(v) => { Node::Format(v) }
+                            VariantFormat::Tuple(v) => { Node::Tuple(v) }
+                            VariantFormat::Struct(v) => { Node::Fields(v) }
+                        };
+
+                        let mut map = serializer.serialize_map(Some(1))?;
+                        map.serialize_entry(&variant.name, &self.child(payload))?;
+                        map.end()
+                    }
+                }
+            }
+        }
+    }
+
+    fn tuple<S: Serializer>(&self, formats: &'s [Format], serializer: S) -> Result<S::Ok, S::Error> {
+        let len = self.read(|d| d.read_tuple_len(formats.len()))?;
+
+        if len != formats.len() {
+            return Err(S::Error::custom(format!("Expected a tuple of {} elements, found {}", formats.len(), len)));
+        }
+
+        let mut tuple = serializer.serialize_tuple(len)?;
+
+        for format in formats {
+            tuple.serialize_element(&self.child(Node::Format(format)))?;
+        }
+
+        tuple.end()
+    }
+
+    fn fields<S: Serializer>(&self, fields: &'s [Field], serializer: S) -> Result<S::Ok, S::Error> {
+        let end = self.read(|d| d.read_struct_end())?;
+
+        // Length-prefixed structs may have fewer fields than the schema
+        let len = match end {
+            Some(_) => { None }
+            None => { Some(fields.len()) }
+        };
+
+        let mut map = serializer.serialize_map(len)?;
+
+        for field in fields {
+            if !self.de.borrow().has_struct_fields(end) {
+                break;
+            }
+
+            map.serialize_entry(&field.name, &self.child(Node::Format(&field.format)))?;
+        }
+
+        self.read(|d| d.finish_struct(end))?;
+
+        map.end()
+    }
+}
+
+/// Every child is one level deeper than its parent, so serializing it counts towards the
+/// deserializer's nesting limit.
+impl Serialize for Transcoder<'_, '_, '_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        self.read(|d| d.enter())?;
+        let res = self.node(serializer);
+        self.de.borrow_mut().leave();
+
+        res
+    }
+}
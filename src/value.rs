@@ -0,0 +1,244 @@
+use std::fmt::Formatter;
+use serde::de::{EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Any value of the serde data model, for decoding without the Rust type at hand.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+
+    F32(f32),
+    F64(f64),
+
+    Char(char),
+    String(String),
+    Bytes(Vec<u8>),
+
+    Option(Option<Box<Value>>),
+    Seq(Vec<Value>),
+    /// Entries in encoded order, keys are not required to be unique or hashable.
+    Map(Vec<(Value, Value)>),
+    /// Variant index and its payload, `Value::Unit` for unit variants.
+    Variant(u32, Box<Value>),
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        match self {
+            Value::Unit => { serializer.serialize_unit() }
+            Value::Bool(v) => { serializer.serialize_bool(*v) }
+            Value::I8(v) => { serializer.serialize_i8(*v) }
+            Value::I16(v) => { serializer.serialize_i16(*v) }
+            Value::I32(v) => { serializer.serialize_i32(*v) }
+            Value::I64(v) => { serializer.serialize_i64(*v) }
+            Value::I128(v) => { serializer.serialize_i128(*v) }
+            Value::U8(v) => { serializer.serialize_u8(*v) }
+            Value::U16(v) => { serializer.serialize_u16(*v) }
+            Value::U32(v) => { serializer.serialize_u32(*v) }
+            Value::U64(v) => { serializer.serialize_u64(*v) }
+            Value::U128(v) => { serializer.serialize_u128(*v) }
+            Value::F32(v) => { serializer.serialize_f32(*v) }
+            Value::F64(v) => { serializer.serialize_f64(*v) }
+            Value::Char(v) => { serializer.serialize_char(*v) }
+            Value::String(v) => { serializer.serialize_str(v) }
+            Value::Bytes(v) => { serializer.serialize_bytes(v) }
+            Value::Option(None) => { serializer.serialize_none() }
+            Value::Option(Some(v)) => { serializer.serialize_some(v) }
+            Value::Seq(v) => {
+                let mut seq = serializer.serialize_seq(Some(v.len()))?;
+
+                for element in v {
+                    seq.serialize_element(element)?;
+                }
+
+                seq.end()
+            }
+            Value::Map(v) => {
+                let mut map = serializer.serialize_map(Some(v.len()))?;
+
+                for (key, value) in v {
+                    map.serialize_entry(key, value)?;
+                }
+
+                map.end()
+            }
+            Value::Variant(index, v) => {
+                // Variant names are unknown, so human-readable formats get the index as a map key
+                if serializer.is_human_readable() {
+                    let mut map = serializer.serialize_map(Some(1))?;
+
+                    map.serialize_entry(index, v)?;
+
+                    return map.end();
+                }
+
+                serializer.serialize_newtype_variant("Value", *index, "", v)
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i8<E>(self, v: i8) -> Result<Self::Value, E> {
+        Ok(Value::I8(v))
+    }
+
+    fn visit_i16<E>(self, v: i16) -> Result<Self::Value, E> {
+        Ok(Value::I16(v))
+    }
+
+    fn visit_i32<E>(self, v: i32) -> Result<Self::Value, E> {
+        Ok(Value::I32(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::I64(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(Value::I128(v))
+    }
+
+    fn visit_u8<E>(self, v: u8) -> Result<Self::Value, E> {
+        Ok(Value::U8(v))
+    }
+
+    fn visit_u16<E>(self, v: u16) -> Result<Self::Value, E> {
+        Ok(Value::U16(v))
+    }
+
+    fn visit_u32<E>(self, v: u32) -> Result<Self::Value, E> {
+        Ok(Value::U32(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Value::U64(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(Value::U128(v))
+    }
+
+    fn visit_f32<E>(self, v: f32) -> Result<Self::Value, E> {
+        Ok(Value::F32(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::F64(v))
+    }
+
+    fn visit_char<E>(self, v: char) -> Result<Self::Value, E> {
+        Ok(Value::Char(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        Ok(Value::Option(Some(Box::new(Value::deserialize(deserializer)?))))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        let mut vec = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(element) = seq.next_element()? {
+            vec.push(element);
+        }
+
+        Ok(Value::Seq(vec))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>
+    {
+        let mut vec = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(entry) = map.next_entry()? {
+            vec.push(entry);
+        }
+
+        Ok(Value::Map(vec))
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>
+    {
+        let (index, variant) = data.variant::<u32>()?;
+
+        Ok(Value::Variant(index, Box::new(variant.newtype_variant()?)))
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::Cursor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use varint_rs::VarintWriter;
+use crate::columnar::Bytes;
+use crate::de;
+use crate::error::SerbfError;
+
+/// `#[serde(with = "serbf::with::delta")]` for a `Vec` of integers that mostly grow by small
+/// steps, like IDs. Writes the first value, then the difference of every value to the one before
+/// it, all as zigzag varints in a single byte string.
+pub mod delta {
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<i128>,
+        S: Serializer
+    {
+        super::serialize(values, false, serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: TryFrom<i128>,
+        D: Deserializer<'de>
+    {
+        super::deserialize(false, deserializer)
+    }
+}
+
+/// `#[serde(with = "serbf::with::delta_of_delta")]` for a `Vec` of integers that grow by a mostly
+/// constant step, like the timestamps of a regular time series. Writes the first value, the first
+/// difference, then how much every difference differs from the one before it.
+pub mod delta_of_delta {
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<i128>,
+        S: Serializer
+    {
+        super::serialize(values, true, serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: TryFrom<i128>,
+        D: Deserializer<'de>
+    {
+        super::deserialize(true, deserializer)
+    }
+}
+
+fn overflow() -> SerbfError {
+    SerbfError::Message("Delta overflows i128".to_string())
+}
+
+fn encode<T>(values: &[T], delta_of_delta: bool) -> Result<Vec<u8>, SerbfError>
+where
+    T: Copy + Into<i128>
+{
+    let mut data = Vec::new();
+    let mut prev = 0i128;
+    let mut prev_delta = 0i128;
+
+    for (i, v) in values.iter().enumerate() {
+        let v = (*v).into();
+        let delta = v.checked_sub(prev).ok_or_else(overflow)?;
+
+        let encoded = match delta_of_delta && i > 1 {
+            true => { delta.checked_sub(prev_delta).ok_or_else(overflow)? }
+            false => { delta }
+        };
+
+        // Writing into a Vec can't fail
+        let _ = data.write_i128_varint(encoded);
+
+        prev = v;
+        prev_delta = delta;
+    }
+
+    Ok(data)
+}
+
+fn decode<T>(data: &[u8], delta_of_delta: bool) -> Result<Vec<T>, SerbfError>
+where
+    T: TryFrom<i128>
+{
+    let mut data = Cursor::new(data);
+    let mut values = Vec::new();
+    let mut prev = 0i128;
+    let mut prev_delta = 0i128;
+
+    while (data.position() as usize) < data.get_ref().len() {
+        let encoded = de::read_zigzag::<i128, _>(&mut data, false)?;
+
+        let delta = match delta_of_delta && values.len() > 1 {
+            true => { prev_delta.checked_add(encoded).ok_or_else(overflow)? }
+            false => { encoded }
+        };
+
+        let v = prev.checked_add(delta).ok_or_else(overflow)?;
+
+        match T::try_from(v) {
+            Ok(v) => { values.push(v) }
+            Err(_) => { return Err(SerbfError::Message(format!("Delta decoded value out of range: {v}"))) }
+        }
+
+        prev = v;
+        prev_delta = delta;
+    }
+
+    Ok(values)
+}
+
+fn serialize<T, S>(values: &[T], delta_of_delta: bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Copy + Into<i128>,
+    S: Serializer
+{
+    match encode(values, delta_of_delta) {
+        Ok(v) => { Bytes(v).serialize(serializer) }
+        Err(e) => { Err(serde::ser::Error::custom(e)) }
+    }
+}
+
+fn deserialize<'de, T, D>(delta_of_delta: bool, deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    T: TryFrom<i128>,
+    D: Deserializer<'de>
+{
+    let data = Bytes::deserialize(deserializer)?;
+
+    match decode(&data.0, delta_of_delta) {
+        Ok(v) => { Ok(v) }
+        Err(e) => { Err(serde::de::Error::custom(e)) }
+    }
+}